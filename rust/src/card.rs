@@ -1,12 +1,18 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use serde::Deserialize;
 use strum_macros::{EnumString, VariantArray, VariantNames};
 use crate::card_db::{CardDB, get_card_db};
 use crate::colors::Color;
 use crate::cost::Cost;
+use crate::counters::{Counters, CounterType};
+use crate::loyalty::{LoyaltyBehavior, LoyaltyError};
+use crate::face_down::{CardView, FaceDownError, FaceDownKind, parse_turn_face_up_cost};
 
 #[derive(
 	Debug, PartialEq, EnumString, Eq, VariantNames,
-	VariantArray, Deserialize
+	VariantArray, Deserialize, Clone, Hash
 )]
 #[strum(serialize_all="lowercase")]
 pub enum CardType {
@@ -46,19 +52,28 @@ pub enum CardType {
 }
 
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 enum TapPurpose{
 	Mana,
 	Action,
 	None
 }
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
 pub enum CardLocation {
+	/// cards that are being played for ante. (it's banned basically everywhere but it's a zone!)
+	Ante,
+	Battlefield,
+	/// where commanders (and emblems, and some other weird stuff) hang out.
+	Command,
 	Exile,
 	Graveyard,
 	Hand,
 	Library,
+	/// cards in your sideboard aren't in the game, but it's still useful to know they're there.
+	Sideboard,
+	/// spells and abilities waiting to resolve.
+	Stack,
 	// TemporaryView, // <- for scrying and surveiling?
 	#[default]
 	None
@@ -76,15 +91,27 @@ enum LandTypes{
 
 
 /// Where is the card, and whether opponents can see it.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct VisibilityBehavior {
 	pub current_location: CardLocation,
-	revealed: bool
+	revealed: bool,
+	/// how many times the card has changed zones. Per the rules (400.7) a card that changes zones
+	/// becomes a new object with no memory of its previous existence, so anything that's holding
+	/// onto a card (like a targeted spell) can compare this to see if it's still the same object.
+	zone_change_count: u32,
 }
 impl VisibilityBehavior{
+	/// moves the card to a new location. Moving to a different zone counts as a zone change;
+	/// "moving" to the zone the card is already in doesn't.
 	pub fn set_location(&mut self, location: CardLocation) {
+		if self.current_location != location {
+			self.zone_change_count += 1;
+		}
 		self.current_location = location;
 	}
+	pub fn zone_change_count(&self) -> u32 {
+		self.zone_change_count
+	}
 	pub fn set_revealed(&mut self, should_reveal: bool){
 		self.revealed = should_reveal;
 	}
@@ -95,7 +122,7 @@ impl VisibilityBehavior{
 
 
 /// How the card enters the battlefield
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct EntranceBehavior{
 	can_have_summoning_sickness: bool,
 	enters_on_instant_stack: bool,
@@ -116,9 +143,15 @@ impl EntranceBehavior{
 			enters_battlefield_tapped,
 		}
 	}
+
+	/// whether the card can be cast any time its controller has priority (it's an instant or has
+	/// flash), instead of only at sorcery speed.
+	pub fn enters_on_instant_stack(&self) -> bool {
+		self.enters_on_instant_stack
+	}
 }
 /// How the card behaves on the battlefield
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct BattlefieldBehavior {
 	/// does it have the ability to attack?
 	can_attack: bool,
@@ -130,6 +163,8 @@ pub struct BattlefieldBehavior {
 	can_turn_face_up: bool,
 	/// is the card face down (disguised?)?
 	is_face_down: bool,
+	/// how it got turned face down, if it is.
+	face_down_as: Option<FaceDownKind>,
 	/// does this card have summoning sickness?
 	is_summon_sick: bool,
 	/// is it tapped?
@@ -140,7 +175,11 @@ pub struct BattlefieldBehavior {
 	power: i32,
 	/// when it blocks how much damage can it take before it dies? (defaults to 0 because some cards
 	/// cant block)
-	toughness: i32
+	toughness: i32,
+	/// the power printed on the card, before counters and stuff get applied.
+	base_power: i32,
+	/// the toughness printed on the card, before counters and stuff get applied.
+	base_toughness: i32,
 }
 
 /// searches a string slice for keywords. keywords in mtgjson data are of the format: {_char_}
@@ -191,7 +230,8 @@ impl BattlefieldBehavior{
 			card.keywords.contains(&"Defender".to_string()) == false;
 		let can_block = card.card_types.contains(&CardType::Creature);
 		let can_tap = card.card_types.contains(&CardType::Land) || can_tap(&card.description);
-		let can_turn_face_up = card.keywords.contains(&"Disguise".to_string());
+		let can_turn_face_up = ["Disguise", "Morph", "Megamorph"].iter()
+			.any(|keyword| card.keywords.contains(&keyword.to_string()));
 		let is_face_down = false;
 		let is_summon_sick = false;
 		let is_tapped = false;
@@ -216,18 +256,57 @@ impl BattlefieldBehavior{
 			can_tap,
 			can_turn_face_up,
 			is_face_down,
+			face_down_as: None,
 			is_summon_sick,
 			is_tapped,
 			tap_purpose,
 			power,
-			toughness
+			toughness,
+			base_power: power,
+			base_toughness: toughness,
 		}
 	}
+
+	/// recalculates power and toughness from the printed values and the +1/+1 and -1/-1 counters.
+	/// face-down cards are 2/2s no matter what's printed on them.
+	pub fn apply_counters(&mut self, counters: &Counters) {
+		let modifier = counters.power_toughness_modifier();
+		let (base_power, base_toughness) = match self.is_face_down {
+			true => (2, 2),
+			false => (self.base_power, self.base_toughness)
+		};
+		self.power = base_power.saturating_add(modifier);
+		self.toughness = base_toughness.saturating_add(modifier);
+	}
+
+	pub fn is_face_down(&self) -> bool {
+		self.is_face_down
+	}
+
+	pub fn face_down_as(&self) -> Option<FaceDownKind> {
+		self.face_down_as
+	}
+
+	pub fn power(&self) -> i32 {
+		self.power
+	}
+
+	pub fn toughness(&self) -> i32 {
+		self.toughness
+	}
+
+	pub fn is_tapped(&self) -> bool {
+		self.is_tapped
+	}
+
+	pub fn set_tapped(&mut self, is_tapped: bool) {
+		self.is_tapped = is_tapped;
+	}
 }
 
 
 /// When the card dies, what does it do?
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ExitBehavior {
 	hits_graveyard_on_death: bool,
 	hits_exile_on_death: bool,
@@ -277,6 +356,9 @@ pub struct Card {
 	#[serde(rename(deserialize = "manaValue"), default)]
 	pub mana_value: u8,
 	pub name: String,
+	/// starting loyalty for planeswalkers. It's a string because some of them are "X".
+	#[serde(default)]
+	pub loyalty: Option<String>,
 	/// power and toughness default to zero as strings; the strings are parsed into numbers in
 	/// RealCard.battlefield_behavior
 	#[serde(default="zero")]
@@ -284,17 +366,57 @@ pub struct Card {
 	pub subtypes: Vec<String>,
 	pub supertypes: Vec<String>,
 	#[serde(default="zero")]
-	pub toughness: String
+	pub toughness: String,
+	/// format name -> "Legal", "Banned" or "Restricted". Formats the card isn't legal in aren't listed.
+	#[serde(default)]
+	pub legalities: HashMap<String, String>,
 }
 
 #[derive(PartialEq, Debug)]
 pub enum RealCardError{
 	CardNotFound(String),
 	InvalidQuantity,
+	/// the card was supposed to be a commander but can't be one.
+	InvalidCommander(String),
 }
 
 
-#[derive(Debug)]
+/// Identifies one physical card in a game: every card in every deck, every token and every copy
+/// gets its own. Outside of a game they're handed out from one counter for the whole program, so
+/// no two cards (not even two players' Forests) ever get the same one. Once a deck's in a game the
+/// game gives its cards new ones (see CardIdAllocator).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CardId(u64);
+
+impl CardId {
+	/// gets a brand new CardId that nothing else has.
+	pub fn generate() -> CardId {
+		static NEXT_CARD_ID: AtomicU64 = AtomicU64::new(1);
+		CardId(NEXT_CARD_ID.fetch_add(1, Ordering::Relaxed))
+	}
+}
+
+/// Hands out CardIds one after another. Every game has its own, so replaying a game's actions
+/// gives its cards and tokens the same ids they had the first time around.
+#[derive(Debug, Clone, Default)]
+pub struct CardIdAllocator {
+	next: u64,
+}
+
+impl CardIdAllocator {
+	pub fn next_id(&mut self) -> CardId {
+		self.next += 1;
+		CardId(self.next)
+	}
+}
+
+impl fmt::Display for CardId {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "#{}", self.0)
+	}
+}
+
+#[derive(Debug, Clone)]
 /// RealCards are cards that we actually interact with; They are created via
 /// ```rust
 /// let card_name = "Forest".to_string();
@@ -303,46 +425,65 @@ pub enum RealCardError{
 /// ```
 /// the ::new searches the card database for that Card, and then creates the various behaviors and
 /// properties we need to do stuff in the game.
-pub struct RealCard <'a>{
-	/// the Card in CardDb
-	pub card: &'a Card,
+pub struct RealCard {
+	/// the Card in CardDb. CardDB is a singleton that never goes away, so every RealCard can just
+	/// point at it instead of borrowing it from somewhere.
+	pub card: &'static Card,
 	/// the card name. case and punctuation matters.
-	pub name: &'a str,
+	pub name: &'static str,
 	/// how many of this card are in your deck.
-	pub quantity: u8,
-	pub key: u8,
+	pub quantity: u32,
+	/// which physical card this is.
+	pub id: CardId,
 	pub visibility_behavior: VisibilityBehavior,
 	pub entrance_behavior: EntranceBehavior,
 	pub battlefield_behavior: BattlefieldBehavior,
 	pub exit_behavior: ExitBehavior,
+	/// counters on the card. (they fall off when it changes zones.)
+	pub counters: Counters,
+	/// tokens aren't real cards; they cease to exist when they leave the battlefield.
+	pub is_token: bool,
+	/// loyalty and loyalty abilities, if the card is a planeswalker.
+	pub loyalty_behavior: Option<LoyaltyBehavior>,
 }
 
 /// determines if a card is a basic land by searching it's card_types and supertypes
-fn card_is_basic_land(card_types: &Vec<CardType>, supertypes: &Vec<String>) -> bool{
+pub fn card_is_basic_land(card_types: &Vec<CardType>, supertypes: &Vec<String>) -> bool{
 	(card_types.contains(&CardType::Land) && supertypes.contains(&"Basic".to_string()))
 }
 
 
 
-impl RealCard<'_>{
-	pub fn new(name: &str, quantity: u8, key:u8)-> Result<RealCard, RealCardError> {
-		let db: &CardDB = get_card_db();
+impl RealCard{
+	pub fn new(name: &str, quantity: u32)-> Result<RealCard, RealCardError> {
+		let db: &'static CardDB = get_card_db();
 		let card_result: Result<&Card, RealCardError> = match db.get_card(name){
 			Ok(card) => Ok(card),
 			Err(e) => Err(RealCardError::CardNotFound(name.to_string()))
 		};
 		let card = card_result?;
 
-		let is_basic_land =  card_is_basic_land(&card.card_types, &card.supertypes);
-
-		// can only have up to 4 of the same card in a deck unless its a basic land.
-		if !is_basic_land && (quantity > 4 || quantity < 1) {
+		// how many copies you're allowed depends on the format; see DeckValidator for that.
+		if quantity < 1 {
 			return Err(RealCardError::InvalidQuantity);
 		}
 
+		Ok(RealCard::from_card(card, quantity, CardLocation::Library))
+	}
+
+	/// makes a token. The Card comes from the token db (see token.rs) since tokens aren't in CardDB.
+	/// Tokens are created on the battlefield.
+	pub fn new_token(card: &'static Card) -> RealCard {
+		let mut token = RealCard::from_card(card, 1, CardLocation::Battlefield);
+		token.is_token = true;
+		token
+	}
+
+	fn from_card(card: &'static Card, quantity: u32, location: CardLocation) -> RealCard {
 		let visibility_behavior = VisibilityBehavior {
-			current_location: CardLocation::Library,
+			current_location: location,
 			revealed: false,
+			zone_change_count: 0,
 		};
 		let entrance_behavior = EntranceBehavior::new(card);
 		let battlefield_behavior = BattlefieldBehavior::new(card);
@@ -351,23 +492,149 @@ impl RealCard<'_>{
 			hits_exile_on_death: false,
 			location_on_death: CardLocation::Graveyard
 		};
-		Ok(
-			RealCard{
-			name,
+		RealCard{
+			name: &card.name,
 			card,
 			quantity,
-			key,
+			id: CardId::generate(),
 			visibility_behavior,
 			entrance_behavior,
 			battlefield_behavior,
-			exit_behavior
+			exit_behavior,
+			counters: Counters::default(),
+			is_token: false,
+			loyalty_behavior: LoyaltyBehavior::new(card),
 		}
-		)
 	}
 
 	/// move card from its current location to a new location.
 	pub fn change_current_location(&mut self, new_location: CardLocation){
+		let old_location = self.visibility_behavior.current_location;
 		self.visibility_behavior.set_location(new_location);
+		// it's a new object now, so it doesn't get to keep its counters.
+		if old_location != new_location {
+			// face-down cards get revealed when they leave the battlefield.
+			self.battlefield_behavior.is_face_down = false;
+			self.battlefield_behavior.face_down_as = None;
+			// permanents come into play untapped unless something says otherwise.
+			self.battlefield_behavior.is_tapped = new_location == CardLocation::Battlefield
+				&& self.entrance_behavior.enters_battlefield_tapped;
+			self.counters = Counters::default();
+			self.battlefield_behavior.apply_counters(&self.counters);
+			// planeswalkers enter with their starting loyalty.
+			if new_location == CardLocation::Battlefield {
+				if let Some(loyalty_behavior) = &mut self.loyalty_behavior {
+					loyalty_behavior.reset_for_new_turn();
+					self.counters.add(CounterType::Loyalty, loyalty_behavior.starting_loyalty);
+				}
+			}
+		}
+	}
+
+	/// turns the card face down. It becomes a 2/2 creature with no name or types.
+	pub fn turn_face_down(&mut self, kind: FaceDownKind){
+		self.battlefield_behavior.is_face_down = true;
+		self.battlefield_behavior.face_down_as = Some(kind);
+		self.battlefield_behavior.apply_counters(&self.counters);
+	}
+
+	/// what it costs to turn the card face up: its morph/disguise cost, or for manifested and cloaked
+	/// creature cards, their mana cost. None if it can't be turned face up.
+	pub fn turn_face_up_cost(&self) -> Option<Cost> {
+		if let Some(cost) = parse_turn_face_up_cost(&self.card.description) {
+			return Some(cost);
+		}
+		match self.battlefield_behavior.face_down_as {
+			Some(FaceDownKind::Manifest) | Some(FaceDownKind::Cloak)
+				if self.card.card_types.contains(&CardType::Creature) => Some(self.card.mana_cost.clone()),
+			_ => None
+		}
+	}
+
+	/// turns a face-down card face up and returns what it costs to do that (paying for it is up to
+	/// whoever's turning it face up).
+	pub fn turn_face_up(&mut self) -> Result<Cost, FaceDownError> {
+		if !self.battlefield_behavior.is_face_down {
+			return Err(FaceDownError::NotFaceDown);
+		}
+		let cost = self.turn_face_up_cost().ok_or(FaceDownError::CantBeTurnedFaceUp)?;
+		self.battlefield_behavior.is_face_down = false;
+		self.battlefield_behavior.face_down_as = None;
+		self.battlefield_behavior.apply_counters(&self.counters);
+		Ok(cost)
+	}
+
+	/// what a player can see about this card. The controller of a face-down card can look at it
+	/// whenever they want; everybody else just sees a 2/2.
+	pub fn view(&self, viewer_is_controller: bool) -> CardView {
+		let is_face_down = self.battlefield_behavior.is_face_down;
+		if is_face_down && !viewer_is_controller {
+			let has_ward = self.battlefield_behavior.face_down_as.is_some_and(|kind| kind.has_ward());
+			return CardView {
+				name: None,
+				card_types: vec![CardType::Creature],
+				subtypes: vec![],
+				keywords: if has_ward { vec!["Ward 2".to_string()] } else { vec![] },
+				power: self.battlefield_behavior.power,
+				toughness: self.battlefield_behavior.toughness,
+				is_face_down,
+			};
+		}
+		CardView {
+			name: Some(self.name.to_string()),
+			card_types: self.card.card_types.clone(),
+			subtypes: self.card.subtypes.clone(),
+			keywords: self.card.keywords.clone(),
+			power: self.battlefield_behavior.power,
+			toughness: self.battlefield_behavior.toughness,
+			is_face_down,
+		}
+	}
+
+	/// activates one of a planeswalker's loyalty abilities, paying for it with loyalty counters.
+	/// `x` is only used for [+X] and [−X] abilities.
+	pub fn activate_loyalty_ability(&mut self, ability_index: usize, x: u32, can_act_at_sorcery_speed: bool)
+		-> Result<(), LoyaltyError> {
+		if self.visibility_behavior.current_location != CardLocation::Battlefield {
+			return Err(LoyaltyError::NotOnBattlefield);
+		}
+		let loyalty = self.counters.get(&CounterType::Loyalty);
+		let loyalty_behavior = self.loyalty_behavior.as_mut().ok_or(LoyaltyError::NotAPlaneswalker)?;
+		let change = loyalty_behavior.activate(ability_index, x, loyalty, can_act_at_sorcery_speed)?;
+		if change > 0 {
+			self.counters.add(CounterType::Loyalty, change as u32);
+		} else {
+			self.counters.remove(&CounterType::Loyalty, (-change) as u32);
+		}
+		Ok(())
+	}
+
+	/// deals damage to the card. Only planeswalkers keep track of damage right now: damage dealt to a
+	/// planeswalker removes that many loyalty counters from it.
+	pub fn take_damage(&mut self, amount: u32){
+		if self.loyalty_behavior.is_some() {
+			self.counters.remove(&CounterType::Loyalty, amount);
+		}
+	}
+
+	/// a planeswalker with no loyalty goes to the graveyard the next time state-based actions are checked.
+	pub fn has_zero_loyalty(&self) -> bool {
+		self.loyalty_behavior.is_some() &&
+			self.visibility_behavior.current_location == CardLocation::Battlefield &&
+			self.counters.get(&CounterType::Loyalty) == 0
+	}
+
+	/// puts counters on the card and updates its power and toughness.
+	pub fn add_counters(&mut self, kind: CounterType, amount: u32){
+		self.counters.add(kind, amount);
+		self.battlefield_behavior.apply_counters(&self.counters);
+	}
+
+	/// removes up to `amount` counters from the card; returns how many actually got removed.
+	pub fn remove_counters(&mut self, kind: &CounterType, amount: u32) -> u32 {
+		let removed = self.counters.remove(kind, amount);
+		self.battlefield_behavior.apply_counters(&self.counters);
+		removed
 	}
 }
 
@@ -411,12 +678,71 @@ mod tests {
 		assert_eq!(res, false);
 	}
 
+	#[test]
+	fn card_ids_are_never_reused(){
+		let ids: Vec<CardId> = (0..300).map(|_| CardId::generate()).collect();
+		let mut unique = ids.clone();
+		unique.sort();
+		unique.dedup();
+		assert_eq!(unique.len(), 300);
+	}
+
+	#[test]
+	fn changing_zones_makes_the_card_a_new_object(){
+		let mut vis_b = VisibilityBehavior::default();
+		vis_b.set_location(CardLocation::Library);
+		vis_b.set_location(CardLocation::Battlefield);
+		assert_eq!(vis_b.zone_change_count(), 2);
+		vis_b.set_location(CardLocation::Battlefield);
+		assert_eq!(vis_b.zone_change_count(), 2);
+		vis_b.set_location(CardLocation::Command);
+		assert_eq!(vis_b.zone_change_count(), 3);
+	}
+
+	#[test]
+	fn tapped_lands_enter_tapped(){
+		let card: &'static Card = Box::leak(Box::new(Card {
+			description: "Selesnya Guildgate enters the battlefield tapped.\n{T}: Add {G} or {W}.".to_string(),
+			card_types: vec![CardType::Land],
+			power: "0".to_string(),
+			toughness: "0".to_string(),
+			..Default::default()
+		}));
+		let mut guildgate = RealCard::from_card(card, 1, CardLocation::Hand);
+		guildgate.change_current_location(CardLocation::Battlefield);
+		assert!(guildgate.battlefield_behavior.is_tapped());
+		guildgate.change_current_location(CardLocation::Hand);
+		assert!(!guildgate.battlefield_behavior.is_tapped());
+
+		let mut forest = RealCard::new("Forest", 1).unwrap();
+		forest.change_current_location(CardLocation::Battlefield);
+		assert!(!forest.battlefield_behavior.is_tapped());
+	}
+
+	#[test]
+	fn plus_one_counters_change_power_and_toughness(){
+		let card = Card {
+			power: "2".to_string(),
+			toughness: "3".to_string(),
+			card_types: vec![CardType::Creature],
+			..Default::default()
+		};
+		let mut battle_b = BattlefieldBehavior::new(&card);
+		let mut counters = Counters::default();
+		counters.add(CounterType::PlusOnePlusOne, 2);
+		battle_b.apply_counters(&counters);
+		assert_eq!((battle_b.power(), battle_b.toughness()), (4, 5));
+		counters.add(CounterType::MinusOneMinusOne, 3);
+		battle_b.apply_counters(&counters);
+		assert_eq!((battle_b.power(), battle_b.toughness()), (1, 2));
+	}
 
 	#[test]
 	fn valid_card_can_be_made() {
 		let vis_b = VisibilityBehavior {
 			current_location: CardLocation::Library,
-			revealed: false
+			revealed: false,
+			zone_change_count: 0,
 		};
 		let entrance_b = EntranceBehavior {
 			can_have_summoning_sickness: false,
@@ -430,10 +756,13 @@ mod tests {
 			can_turn_face_up: false,
 			is_tapped: false,
 			is_face_down: false,
+			face_down_as: None,
 			is_summon_sick: false,
 			tap_purpose: vec![TapPurpose::Mana],
 			power: 0,
 			toughness: 0,
+			base_power: 0,
+			base_toughness: 0,
 		};
 		let exit_b = ExitBehavior {
 			hits_graveyard_on_death: true,
@@ -452,10 +781,12 @@ mod tests {
 			mana_cost: crate::cost::parse_costs(""),
 			mana_value: 1,
 			name: "Forest".to_string(),
+			loyalty: None,
 			power: "0".to_string(),
 			subtypes: vec![],
 			supertypes: vec![],
 			toughness: "0".to_string(),
+			legalities: HashMap::new(),
 		};
 		assert_eq!(card.name, "Forest");
 	}
@@ -0,0 +1,94 @@
+use crate::card::{Card, CardType};
+use crate::colors::Color;
+use crate::cost::Cost;
+
+/// how much more a commander costs for each time it's already been cast from the command zone.
+pub const COMMANDER_TAX: u8 = 2;
+/// a player who's been dealt this much combat damage by the same commander loses the game.
+pub const COMMANDER_DAMAGE_TO_LOSE: u32 = 21;
+
+#[derive(Debug, PartialEq)]
+pub enum CommanderError {
+	NotInDeck,
+	/// it isn't a legendary creature (and doesn't say it can be your commander).
+	CantBeCommander(String),
+	/// you only get one commander, or two if they're partners (or a commander and a background).
+	TooManyCommanders,
+	NotACommander,
+	NotInCommandZone,
+}
+
+/// whether a card can be a commander all by itself.
+pub fn can_be_commander(card: &Card) -> bool {
+	let legendary_creature = card.supertypes.contains(&"Legendary".to_string())
+		&& card.card_types.contains(&CardType::Creature);
+	legendary_creature || card.description.contains("can be your commander")
+}
+
+pub fn has_partner(card: &Card) -> bool {
+	card.keywords.iter().any(|keyword| keyword.starts_with("Partner"))
+}
+
+fn chooses_a_background(card: &Card) -> bool {
+	card.description.to_lowercase().contains("choose a background")
+}
+
+/// Backgrounds are legendary enchantments that can only be a commander alongside a creature that
+/// says "Choose a Background".
+pub fn is_background(card: &Card) -> bool {
+	card.subtypes.contains(&"Background".to_string())
+}
+
+/// whether two cards can share the command zone as commanders.
+pub fn can_be_commanders_together(first: &Card, second: &Card) -> bool {
+	(has_partner(first) && has_partner(second))
+		|| (chooses_a_background(first) && is_background(second))
+		|| (is_background(first) && chooses_a_background(second))
+}
+
+/// what it costs to cast a commander from the command zone after it's been cast from there
+/// `times_cast` times already. The tax tops out at the most generic mana a cost can hold.
+pub fn cost_with_commander_tax(mana_cost: &Cost, times_cast: u32) -> Cost {
+	let mut cost = mana_cost.clone();
+	let tax = u8::try_from((COMMANDER_TAX as u32).saturating_mul(times_cast)).unwrap_or(u8::MAX);
+	if tax > 0 {
+		cost.cost.remove(&Color::None);
+		let generic = cost.cost.entry(Color::C).or_insert(0);
+		*generic = generic.saturating_add(tax);
+	}
+	cost
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::cost::parse_costs_better;
+
+	#[test]
+	fn commander_tax_is_two_generic_per_cast(){
+		let cost = cost_with_commander_tax(&parse_costs_better("{2}{R}{W}"), 2);
+		assert_eq!(cost.cost.get(&Color::C), Some(&6));
+		assert_eq!(cost.cost.get(&Color::R), Some(&1));
+		let cost = cost_with_commander_tax(&parse_costs_better("{R}"), 0);
+		assert_eq!(cost.cost.get(&Color::C), None);
+	}
+
+	#[test]
+	fn commander_tax_doesnt_wrap_around(){
+		let cost = cost_with_commander_tax(&parse_costs_better("{2}{R}{W}"), 200);
+		assert_eq!(cost.cost.get(&Color::C), Some(&u8::MAX));
+		let cost = cost_with_commander_tax(&parse_costs_better("{2}{R}{W}"), u32::MAX);
+		assert_eq!(cost.cost.get(&Color::C), Some(&u8::MAX));
+		assert_eq!(cost.cost.get(&Color::R), Some(&1));
+	}
+
+	#[test]
+	fn partners_and_backgrounds_can_share_the_command_zone(){
+		let partner = Card { keywords: vec!["Partner".to_string()], ..Default::default() };
+		let chooser = Card { description: "Choose a Background".to_string(), ..Default::default() };
+		let background = Card { subtypes: vec!["Background".to_string()], ..Default::default() };
+		assert!(can_be_commanders_together(&partner, &partner));
+		assert!(can_be_commanders_together(&background, &chooser));
+		assert!(!can_be_commanders_together(&partner, &background));
+	}
+}
@@ -2,6 +2,8 @@ use serde::{Deserialize, Deserializer};
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::OnceLock;
+use crate::card::Card;
 use crate::colors::Color;
 
 #[derive(Debug, Deserialize)]
@@ -10,7 +12,7 @@ pub struct Payment{
 	quantity: u8
 }
 
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, PartialEq, Default, Clone)]
 pub struct Cost {
 	pub cost: HashMap<Color, u8>,
 	// has_variable_cost: bool
@@ -131,9 +133,114 @@ pub fn parse_costs_better(mana_cost: &str) -> Cost{
 
 
 
+/// One mana some source can make, as the colors it's allowed to be. A land that taps for any color
+/// is all five; Sol Ring is two sources that are both just colorless.
+pub type ManaSource = Vec<Color>;
+
+/// the mana a permanent can make, one ManaSource per mana, from its "Add ..." abilities. Mana
+/// it costs to activate (like a Signet's {1}) comes off the top, and what's left can be any of
+/// the colors the ability makes.
+pub fn mana_sources(card: &Card) -> Vec<ManaSource> {
+	static SYMBOL: OnceLock<Regex> = OnceLock::new();
+	static GENERIC: OnceLock<Regex> = OnceLock::new();
+	let symbol = SYMBOL.get_or_init(|| Regex::new(r"\{([WUBRGC])}").unwrap());
+	let generic = GENERIC.get_or_init(|| Regex::new(r"\{(\d+)}").unwrap());
+	let mut sources = vec![];
+	for line in card.description.lines() {
+		let (activation, produced) = match line.split_once("Add ") {
+			Some(split) => split,
+			None => continue,
+		};
+		let produced = produced.split('.').next().unwrap_or("");
+		let (colors, amount): (Vec<Color>, usize) = if produced.contains("any color") {
+			(vec![Color::W, Color::U, Color::B, Color::R, Color::G], 1)
+		} else {
+			let symbols: Vec<Color> = symbol.captures_iter(produced).map(|symbol| symbol[1].parse().unwrap()).collect();
+			let amount = symbols.len();
+			let mut colors = vec![];
+			for color in symbols {
+				if !colors.contains(&color) {
+					colors.push(color);
+				}
+			}
+			(colors, amount)
+		};
+		let activation_cost: usize = generic.captures_iter(activation).map(|cost| cost[1].parse::<usize>().unwrap()).sum();
+		sources.extend((0..amount.saturating_sub(activation_cost)).map(|_| colors.clone()));
+	}
+	sources
+}
+
+/// a mana symbol that has to be paid with a particular color.
+enum Pip {
+	/// any one of these colors, like {R} or {R/W}.
+	Of(Vec<Color>),
+	/// {2/U}: the color, or two of anything.
+	OrTwoGeneric(Color),
+}
+
+/// figures out which sources to tap to pay for a cost. Returns the indexes of the sources to tap,
+/// or None if they can't pay for it. X is 0, Phyrexian mana gets paid with life, and generic
+/// (and snow) mana can be paid with anything.
+pub fn pay_cost(cost: &Cost, sources: &[ManaSource]) -> Option<Vec<usize>> {
+	let mut pips = vec![];
+	let mut generic = 0;
+	for (color, amount) in cost.cost.iter() {
+		let amount = *amount as usize;
+		match color {
+			Color::C | Color::S => generic += amount,
+			Color::X | Color::P | Color::None => {},
+			Color::MultiColor { colors, .. } if colors.contains(&Color::P) => {},
+			Color::MultiColor { colors, .. } if colors.contains(&Color::C) => {
+				let color = colors.iter().find(|color| **color != Color::C).unwrap();
+				pips.extend((0..amount).map(|_| Pip::OrTwoGeneric(color.clone())));
+			},
+			Color::MultiColor { colors, .. } => pips.extend((0..amount).map(|_| Pip::Of(colors.clone()))),
+			color => pips.extend((0..amount).map(|_| Pip::Of(vec![color.clone()]))),
+		}
+	}
+	let mut used = vec![false; sources.len()];
+	if !assign_pips(&pips, sources, &mut used, generic) {
+		return None;
+	}
+	Some(used.iter().enumerate().filter(|(_, used)| **used).map(|(i, _)| i).collect())
+}
+
+/// tries every way of paying for the colored pips (there are never many) until the generic mana
+/// fits in what's left over. `used` ends up with the sources that got tapped.
+fn assign_pips(pips: &[Pip], sources: &[ManaSource], used: &mut Vec<bool>, generic: usize) -> bool {
+	let (pip, rest) = match pips.split_first() {
+		Some(split) => split,
+		None => {
+			let unused: Vec<usize> = (0..sources.len()).filter(|i| !used[*i]).take(generic).collect();
+			if unused.len() < generic {
+				return false;
+			}
+			unused.iter().for_each(|i| used[*i] = true);
+			return true;
+		}
+	};
+	let colors = match pip {
+		Pip::Of(colors) => colors.clone(),
+		Pip::OrTwoGeneric(color) => vec![color.clone()],
+	};
+	for i in 0..sources.len() {
+		if used[i] || !sources[i].iter().any(|color| colors.contains(color)) {
+			continue;
+		}
+		used[i] = true;
+		if assign_pips(rest, sources, used, generic) {
+			return true;
+		}
+		used[i] = false;
+	}
+	matches!(pip, Pip::OrTwoGeneric(_)) && assign_pips(rest, sources, used, generic + 2)
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::card_db::get_card_db;
 	#[test]
 	fn parse_costs_better_works(){
 		let str = "{G}";
@@ -251,6 +358,21 @@ mod tests {
 		assert_eq!(*cost.cost.get(&Color::G).unwrap(), 4);
 	}
 
+	#[test]
+	fn pay_cost_finds_a_way_to_pay(){
+		let w = vec![Color::W];
+		let r = vec![Color::R];
+		let boros = vec![Color::R, Color::W];
+		let cost = parse_costs_better("{2}{R}{W}");
+		assert_eq!(pay_cost(&cost, &[w.clone(), boros.clone(), w.clone()]), None);
+		// the Boros source has to be the red one.
+		let paid = pay_cost(&cost, &[boros.clone(), w.clone(), w.clone(), w.clone()]).unwrap();
+		assert_eq!(paid.len(), 4);
+		assert_eq!(pay_cost(&parse_costs_better("{2/U}"), &[r.clone(), r.clone()]).unwrap().len(), 2);
+		assert_eq!(pay_cost(&parse_costs_better("{R/W}{R/W}"), &[w, r]).unwrap().len(), 2);
+		assert_eq!(pay_cost(&parse_costs_better(""), &[]), Some(vec![]));
+	}
+
 	#[test]
 	fn cost_accepts_none_payments() {
 		let cost = Cost::new(vec![]);
@@ -258,4 +380,14 @@ mod tests {
 		assert_eq!(cost.cost.contains_key(&Color::G), false);
 		assert_eq!(cost.cost.contains_key(&Color::None), true);
 	}
+
+	#[test]
+	fn mana_sources_come_from_mana_abilities(){
+		let db = get_card_db();
+		assert_eq!(mana_sources(db.get_card("Mountain").unwrap()), vec![vec![Color::R]]);
+		assert_eq!(mana_sources(db.get_card("Sol Ring").unwrap()), vec![vec![Color::C], vec![Color::C]]);
+		assert_eq!(mana_sources(db.get_card("Boros Signet").unwrap()), vec![vec![Color::R, Color::W]]);
+		assert_eq!(mana_sources(db.get_card("Command Tower").unwrap())[0].len(), 5);
+		assert!(mana_sources(db.get_card("Lightning Bolt").unwrap()).is_empty());
+	}
 }
\ No newline at end of file
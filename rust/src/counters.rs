@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+/// The kinds of counters that can be put on permanents and players. There are a LOT of weird
+/// counters in magic (muster counters, brick counters, you name it) so the ones that don't have
+/// any special rules attached to them are just Named.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CounterType {
+	/// +1/+1 counters; each one gives the permanent +1/+1.
+	PlusOnePlusOne,
+	/// -1/-1 counters; each one gives the permanent -1/-1.
+	MinusOneMinusOne,
+	Loyalty,
+	Charge,
+	Lore,
+	Time,
+	/// if a permanent with a shield counter would be dealt damage or destroyed, remove a shield
+	/// counter instead.
+	Shield,
+	/// if a permanent with a stun counter would become untapped, remove a stun counter instead.
+	Stun,
+	/// a player with 10 or more poison counters loses the game.
+	Poison,
+	Energy,
+	/// any other kind of counter, like "muster" or "brick".
+	Named(String),
+}
+
+/// A bag of counters; both RealCards and Players have one.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Counters {
+	counters: HashMap<CounterType, u32>,
+}
+
+impl Counters {
+	/// how many counters of a given kind there are.
+	pub fn get(&self, kind: &CounterType) -> u32 {
+		*self.counters.get(kind).unwrap_or(&0)
+	}
+
+	/// puts counters on the thing. +1/+1 and -1/-1 counters annihilate each other
+	/// (this is technically a state-based action, but nothing can happen in between so we just do it
+	/// right away).
+	pub fn add(&mut self, kind: CounterType, amount: u32) {
+		if amount == 0 {
+			return;
+		}
+		let count = self.counters.entry(kind).or_insert(0);
+		*count = count.saturating_add(amount);
+		self.annihilate();
+	}
+
+	/// removes up to `amount` counters of a kind and returns how many were actually removed.
+	pub fn remove(&mut self, kind: &CounterType, amount: u32) -> u32 {
+		let current = self.get(kind);
+		let removed = current.min(amount);
+		if current == removed {
+			self.counters.remove(kind);
+		} else {
+			self.counters.insert(kind.clone(), current - removed);
+		}
+		removed
+	}
+
+	/// every kind of counter that's on the thing.
+	pub fn kinds(&self) -> Vec<&CounterType> {
+		self.counters.keys().collect()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.counters.is_empty()
+	}
+
+	/// gives the thing another counter of each kind already there. (Proliferate lets you choose
+	/// which permanents and players get counters; whoever is choosing calls this on each of them.)
+	pub fn proliferate(&mut self) {
+		let kinds: Vec<CounterType> = self.counters.keys().cloned().collect();
+		for kind in kinds {
+			self.add(kind, 1);
+		}
+	}
+
+	/// how much the +1/+1 and -1/-1 counters change power and toughness by.
+	pub fn power_toughness_modifier(&self) -> i32 {
+		let count = |kind| i32::try_from(self.get(kind)).unwrap_or(i32::MAX);
+		count(&CounterType::PlusOnePlusOne) - count(&CounterType::MinusOneMinusOne)
+	}
+
+	fn annihilate(&mut self) {
+		let plus = self.get(&CounterType::PlusOnePlusOne);
+		let minus = self.get(&CounterType::MinusOneMinusOne);
+		let both = plus.min(minus);
+		if both > 0 {
+			self.remove(&CounterType::PlusOnePlusOne, both);
+			self.remove(&CounterType::MinusOneMinusOne, both);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn counters_can_be_added_and_removed(){
+		let mut counters = Counters::default();
+		counters.add(CounterType::Charge, 3);
+		assert_eq!(counters.get(&CounterType::Charge), 3);
+		assert_eq!(counters.remove(&CounterType::Charge, 5), 3);
+		assert_eq!(counters.get(&CounterType::Charge), 0);
+		assert!(counters.is_empty());
+	}
+
+	#[test]
+	fn plus_one_and_minus_one_counters_annihilate(){
+		let mut counters = Counters::default();
+		counters.add(CounterType::PlusOnePlusOne, 3);
+		counters.add(CounterType::MinusOneMinusOne, 2);
+		assert_eq!(counters.get(&CounterType::PlusOnePlusOne), 1);
+		assert_eq!(counters.get(&CounterType::MinusOneMinusOne), 0);
+		assert_eq!(counters.power_toughness_modifier(), 1);
+	}
+
+	#[test]
+	fn proliferate_adds_one_of_each_kind(){
+		let mut counters = Counters::default();
+		counters.add(CounterType::Poison, 2);
+		counters.add(CounterType::Named("muster".to_string()), 1);
+		counters.proliferate();
+		assert_eq!(counters.get(&CounterType::Poison), 3);
+		assert_eq!(counters.get(&CounterType::Named("muster".to_string())), 2);
+		assert_eq!(counters.get(&CounterType::Energy), 0);
+	}
+
+	#[test]
+	fn counters_stop_at_the_most_there_can_be(){
+		let mut counters = Counters::default();
+		counters.add(CounterType::PlusOnePlusOne, u32::MAX - 1);
+		counters.proliferate();
+		counters.proliferate();
+		assert_eq!(counters.get(&CounterType::PlusOnePlusOne), u32::MAX);
+		assert_eq!(counters.power_toughness_modifier(), i32::MAX);
+	}
+}
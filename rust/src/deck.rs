@@ -1,154 +1,837 @@
-use std::cell::{Ref, RefCell, RefMut};
 use std::collections::HashMap;
-use std::rc::Rc;
-use crate::card::{CardLocation, RealCard, RealCardError};
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use std::sync::OnceLock;
+use regex::Regex;
+use crate::card::{CardId, CardIdAllocator, CardLocation, RealCard, RealCardError};
+use crate::rng::GameRng;
 use crate::state_manager::{Player};
+use crate::token::{get_token_card, TokenDefinition};
+use crate::cost::Cost;
+use crate::commander::{can_be_commander, can_be_commanders_together, cost_with_commander_tax, is_background, CommanderError};
+use crate::deck_list::DeckList;
+use crate::deck_validation::{DeckValidator, DeckViolation};
+use crate::face_down::{FaceDownError, FaceDownKind};
+use crate::reducers::{CurrentEvent, EventSource};
 
 /// a tuple with the card_name and the number of that card you want in your library/sideboard.
 /// ("Forest", 20)
-pub struct CardListItem(pub String, pub u8);
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardListItem(pub String, pub u32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SideboardError {
+	/// there aren't that many copies of the card in the library (or sideboard) to swap.
+	NotEnoughCopies(String),
+	/// the deck wouldn't be legal after the swap.
+	IllegalDeck(Vec<DeckViolation>),
+}
+
+/// how many cards you draw for your opening hand (and every time you mulligan).
+pub const OPENING_HAND_SIZE: usize = 7;
+
+/// What a player is doing with the cards they're looking at in their library.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LookKind {
+	/// put them back on top or on the bottom, in any order.
+	Scry,
+	/// put them back on top in any order, or into the graveyard.
+	Surveil,
+	/// just look, then put them back on top in any order.
+	Look,
+	/// find some of them to take out of the library; it gets shuffled after.
+	Search,
+}
+
+/// Cards from the library a player is looking at and still has to decide what to do with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingLook {
+	pub kind: LookKind,
+	pub card_ids: Vec<CardId>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LibraryError {
+	/// nobody's looking at any cards right now (or they're looking at them for something else).
+	NothingPending,
+	/// the cards chosen aren't exactly the cards being looked at.
+	WrongCards,
+	/// the cards can't go there; like putting scried cards in the graveyard.
+	NotAllowed,
+	/// the same card was picked more than once.
+	SameCardTwice,
+	/// moving one of the cards failed.
+	Zone(ZoneError),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ZoneError {
+	/// there's no card with that id in this deck.
+	CardNotFound,
+	/// the card isn't in the zone it was supposed to be moved from.
+	NotInZone { expected: CardLocation, actual: CardLocation },
+	/// there's no zone in the deck for that location (CardLocation::None).
+	NoSuchZone,
+	/// cards can't be moved that way; like returning a card to hand from the library.
+	NotAllowed,
+	/// the same card was picked more than once.
+	SameCardTwice,
+}
 
 /// Deck is a players' library of cards they can play. It is its' own state manager.
 /// cards is a hashmap of cards so that when someone searches
 /// for cards, we can give them the option to type the card theyre
 /// looking for and look it up in constant time.
 /// sideboard is optional
-#[derive(Debug)]
-pub struct Deck<'a> {
+///
+/// The RealCards themselves only live in `cards`; every zone is just an ordered list of the ids
+/// of the cards in it. Nothing in here borrows anything, so a Deck can be cloned, sent between
+/// threads and kept in the GameState.
+#[derive(Debug, Clone, Default)]
+pub struct Deck {
 	/// library is the actual library people will draw from; it can be shuffled and milled, etc.
 	/// if a RealCard in cards has a quantity of 4, there will be 4 copies of the RealCard in library.
-	/// order matters.
-	pub library: Vec<Rc<RefCell<RealCard<'a>>>>,
-	/// cards is a hashmap that contains all the cards in your library. Used to get cards in
-	/// constant time.
-	pub cards: HashMap<(String, u8), Rc<RefCell<RealCard<'a>>>>,
-	/// sideboard is a hashmap of cards in your sideboard.
-	// TODO add a function that moves cards from your library to your sideboard and vise-versa.
-	pub sideboard: HashMap<String, Rc<RefCell<RealCard<'a>>>>,
-	pub graveyard: Vec<Rc<RefCell<RealCard<'a>>>>,
-	pub exile: Vec<Rc<RefCell<RealCard<'a>>>>,
+	/// order matters; the top of the library is the end of the vec.
+	pub library: Vec<CardId>,
+	/// cards is a hashmap that contains all the cards in your deck (sideboard included). Used to
+	/// get cards in constant time.
+	pub cards: HashMap<CardId, RealCard>,
+	/// the cards in your sideboard. Use swap_sideboard_cards to move cards between it and the library.
+	pub sideboard: Vec<CardId>,
+	/// the cards in the player's hand, in the order they got there.
+	pub hand: Vec<CardId>,
+	/// the sideboard the deck was registered with, so it can be put back after a match.
+	pub registered_sideboard: Vec<CardId>,
+	pub graveyard: Vec<CardId>,
+	pub exile: Vec<CardId>,
+	/// permanents this player owns that are on the battlefield.
+	pub battlefield: Vec<CardId>,
+	/// the command zone; commanders live here when they aren't out doing commander stuff.
+	pub command: Vec<CardId>,
+	/// cards anted up for this game.
+	pub ante: Vec<CardId>,
+	/// this player's spells that have been cast and haven't resolved yet.
+	pub stack: Vec<CardId>,
 	pub player: Player,
+	/// this deck's commander(s), if it's a commander deck. They're normal cards in `cards`; this
+	/// just says which ones they are.
+	pub commanders: Vec<CardId>,
+	/// how many times each commander has been cast from the command zone, for commander tax.
+	pub commander_casts: HashMap<CardId, u32>,
+	/// the deck's companion, if it has one. It's a normal card in the sideboard; this just says
+	/// which one it is.
+	pub companion: Option<CardId>,
+	/// whether commanders go back to the command zone instead of to the graveyard, exile, hand or
+	/// library. It's the owner's choice; this is the choice they've made.
+	pub commanders_return_to_command_zone: bool,
+	/// cards the player is looking at for a scry, surveil, etc. and hasn't put anywhere yet.
+	/// They're still in the library until they do.
+	pub pending_look: Option<PendingLook>,
 }
 
-impl<'a> Deck<'a>{
+impl Deck{
 	/// Accepts 2 vectors of cardnames and quantities like:
 	/// Library::new([("Forest", 20),("Swamp", 15), ("Insidious Roots", 4)], [/* optional sideboard */])
 	/// and converts them into a Library.
 	pub fn new(
 		player: Player,
-		card_list: &'a Vec<CardListItem>,
-		sideboard_list: &'a Vec<CardListItem>)
-		-> Result<Deck<'a>, RealCardError>{
+		card_list: &Vec<CardListItem>,
+		sideboard_list: &Vec<CardListItem>)
+		-> Result<Deck, RealCardError>{
 
 
 		let mut cards = HashMap::new();
 		let mut library = vec![];
-		let mut sideboard= HashMap::new();
+		let mut sideboard = vec![];
 		let exile = vec![];
 		let graveyard = vec![];
+		let battlefield = vec![];
+		let command = vec![];
+		let ante = vec![];
+		let stack = vec![];
 
 		for card in card_list.iter(){
 			let (card_name, qty) = (&card.0, card.1);
-			for i in (0..qty){
-				let real_card = Rc::new(RefCell::new(RealCard::new(card_name, qty, i)?));
-				let real_card_ref = Rc::clone(&real_card);
-
-				library.push(real_card_ref); // todo: problem... i don't know which card (i) this is in library.
-				cards.insert((card_name.to_string(), i), real_card);
+			for _ in (0..qty){
+				let real_card = RealCard::new(card_name, qty)?;
+				library.push(real_card.id);
+				cards.insert(real_card.id, real_card);
 			}
 
 		}
 		for card in sideboard_list.iter(){
 			let (card_name, qty) = (&card.0, card.1);
-			let real_card = Rc::new(RefCell::new(RealCard::new(card_name, qty, qty)?));
-			sideboard.insert(card_name.to_string(), real_card);
+			for _ in (0..qty){
+				let mut real_card = RealCard::new(card_name, qty)?;
+				real_card.change_current_location(CardLocation::Sideboard);
+				sideboard.push(real_card.id);
+				cards.insert(real_card.id, real_card);
+			}
 		}
 
 		Ok(Deck {
 			library,
 			cards,
+			registered_sideboard: sideboard.clone(),
 			sideboard,
+			hand: vec![],
 			player,
 			exile,
-			graveyard
+			graveyard,
+			battlefield,
+			command,
+			ante,
+			stack,
+			commanders: vec![],
+			commander_casts: HashMap::new(),
+			companion: None,
+			commanders_return_to_command_zone: true,
+			pending_look: None,
 		})
 	}
 
+	/// gets the ordered zone that cards in a given location are kept in. Returns None for
+	/// CardLocation::None, since that isn't a zone.
+	pub fn zone(&self, location: CardLocation) -> Option<&Vec<CardId>> {
+		match location {
+			CardLocation::Ante => Some(&self.ante),
+			CardLocation::Battlefield => Some(&self.battlefield),
+			CardLocation::Command => Some(&self.command),
+			CardLocation::Exile => Some(&self.exile),
+			CardLocation::Graveyard => Some(&self.graveyard),
+			CardLocation::Hand => Some(&self.hand),
+			CardLocation::Library => Some(&self.library),
+			CardLocation::Sideboard => Some(&self.sideboard),
+			CardLocation::Stack => Some(&self.stack),
+			CardLocation::None => None
+		}
+	}
+
+	fn zone_mut(&mut self, location: CardLocation) -> Option<&mut Vec<CardId>> {
+		match location {
+			CardLocation::Ante => Some(&mut self.ante),
+			CardLocation::Battlefield => Some(&mut self.battlefield),
+			CardLocation::Command => Some(&mut self.command),
+			CardLocation::Exile => Some(&mut self.exile),
+			CardLocation::Graveyard => Some(&mut self.graveyard),
+			CardLocation::Hand => Some(&mut self.hand),
+			CardLocation::Library => Some(&mut self.library),
+			CardLocation::Sideboard => Some(&mut self.sideboard),
+			CardLocation::Stack => Some(&mut self.stack),
+			CardLocation::None => None
+		}
+	}
+
+	/// which zone a card is in right now, or None if it isn't in this deck.
+	pub fn location_of(&self, card_id: CardId) -> Option<CardLocation> {
+		self.cards.get(&card_id).map(|card| card.visibility_behavior.current_location)
+	}
+
+	/// moves a card from one zone to the top of another, keeping the zones and the card's
+	/// visibility_behavior in step. Fails (and moves nothing) if the card isn't in `from`.
+	/// Commanders headed to the graveyard, exile, hand or library go to the command zone instead if
+	/// `commanders_return_to_command_zone` is set.
+	pub fn move_card(&mut self, card_id: CardId, from: CardLocation, to: CardLocation) -> Result<(), ZoneError> {
+		let actual = self.location_of(card_id).ok_or(ZoneError::CardNotFound)?;
+		if actual != from || !self.zone(from).ok_or(ZoneError::NoSuchZone)?.contains(&card_id) {
+			return Err(ZoneError::NotInZone { expected: from, actual });
+		}
+		let to = match to {
+			CardLocation::Graveyard | CardLocation::Exile | CardLocation::Hand | CardLocation::Library
+				if self.commanders_return_to_command_zone && self.is_commander(card_id) => CardLocation::Command,
+			_ => to
+		};
+		if self.zone(to).is_none() {
+			return Err(ZoneError::NoSuchZone);
+		}
+		self.zone_mut(from).unwrap().retain(|id| *id != card_id);
+		self.zone_mut(to).unwrap().push(card_id);
+		self.cards.get_mut(&card_id).unwrap().change_current_location(to);
+		Ok(())
+	}
+
+	/// move_card for when you don't already know where the card is.
+	fn move_card_from_anywhere(&mut self, card_id: CardId, to: CardLocation) -> Result<(), ZoneError> {
+		let from = self.location_of(card_id).ok_or(ZoneError::CardNotFound)?;
+		self.move_card(card_id, from, to)
+	}
+
+	/// whether every card in the deck is in exactly one zone, and it's the zone the card thinks
+	/// it's in.
+	pub fn zones_are_consistent(&self) -> bool {
+		let locations = [CardLocation::Ante, CardLocation::Battlefield, CardLocation::Command,
+			CardLocation::Exile, CardLocation::Graveyard, CardLocation::Hand, CardLocation::Library,
+			CardLocation::Sideboard, CardLocation::Stack];
+		let mut seen = 0;
+		for location in locations {
+			for card_id in self.zone(location).unwrap().iter() {
+				match self.cards.get(card_id) {
+					Some(card) if card.visibility_behavior.current_location == location => seen += 1,
+					_ => return false,
+				}
+			}
+		}
+		seen == self.cards.len()
+	}
+
+	/// makes a card in this deck one of its commanders and puts it in the command zone. A deck can
+	/// have one commander, or two if they're partners or a commander and its background.
+	pub fn designate_commander(&mut self, card_id: CardId) -> Result<(), CommanderError> {
+		let card = self.cards.get(&card_id).ok_or(CommanderError::NotInDeck)?.card;
+		match self.commanders.as_slice() {
+			[] if !can_be_commander(card) && !is_background(card) =>
+				return Err(CommanderError::CantBeCommander(card.name.clone())),
+			[] => {},
+			[first] if !can_be_commanders_together(self.cards[first].card, card) =>
+				return Err(CommanderError::TooManyCommanders),
+			[_] => {},
+			_ => return Err(CommanderError::TooManyCommanders),
+		}
+		self.commanders.push(card_id);
+		self.move_card_from_anywhere(card_id, CardLocation::Command).map_err(|_| CommanderError::NotInDeck)?;
+		Ok(())
+	}
+
+	pub fn is_commander(&self, card_id: CardId) -> bool {
+		self.commanders.contains(&card_id)
+	}
+
+	/// how much extra generic mana the commander costs to cast from the command zone right now.
+	pub fn commander_tax(&self, card_id: CardId) -> u32 {
+		*self.commander_casts.get(&card_id).unwrap_or(&0) * crate::commander::COMMANDER_TAX as u32
+	}
+
+	/// casts a commander from the command zone; returns what it costs (commander tax included)
+	/// and counts the cast towards the tax for next time.
+	pub fn cast_commander(&mut self, card_id: CardId) -> Result<Cost, CommanderError> {
+		if !self.is_commander(card_id) {
+			return Err(CommanderError::NotACommander);
+		}
+		if !self.command.contains(&card_id) {
+			return Err(CommanderError::NotInCommandZone);
+		}
+		let times_cast = self.commander_casts.entry(card_id).or_insert(0);
+		let cost = cost_with_commander_tax(&self.cards[&card_id].card.mana_cost, *times_cast);
+		*times_cast += 1;
+		self.move_card(card_id, CardLocation::Command, CardLocation::Stack).unwrap();
+		Ok(cost)
+	}
+
+	/// sideboarding between games: moves `take_out` from the library to the sideboard and
+	/// `bring_in` from the sideboard to the library. If a validator is given and the deck wouldn't
+	/// be legal afterwards, nothing gets moved.
+	pub fn swap_sideboard_cards(
+		&mut self,
+		take_out: &[CardListItem],
+		bring_in: &[CardListItem],
+		validator: Option<&DeckValidator>)
+		-> Result<(), SideboardError> {
+		let mut swapped = self.clone();
+		for item in take_out.iter() {
+			swapped.move_copies(item, CardLocation::Library, CardLocation::Sideboard)?;
+		}
+		for item in bring_in.iter() {
+			swapped.move_copies(item, CardLocation::Sideboard, CardLocation::Library)?;
+		}
+		if let Some(validator) = validator {
+			validator.validate(&DeckList::from_deck(&swapped)).map_err(SideboardError::IllegalDeck)?;
+		}
+		*self = swapped;
+		Ok(())
+	}
+
+	/// puts the deck back the way it was registered: everything that started in the sideboard goes
+	/// back there and everything else goes back in the library. (Commanders go back to the command
+	/// zone.) Tokens are gone.
+	pub fn restore_registered_list(&mut self) {
+		self.cards.retain(|_, card| !card.is_token);
+		let ids: Vec<CardId> = self.cards.keys().cloned().collect();
+		for zone in [&mut self.library, &mut self.sideboard, &mut self.hand, &mut self.graveyard,
+			&mut self.exile, &mut self.battlefield, &mut self.command, &mut self.ante, &mut self.stack] {
+			zone.clear();
+		}
+		for card_id in ids {
+			let location = if self.is_commander(card_id) {
+				CardLocation::Command
+			} else if self.registered_sideboard.contains(&card_id) {
+				CardLocation::Sideboard
+			} else {
+				CardLocation::Library
+			};
+			self.cards.get_mut(&card_id).unwrap().change_current_location(location);
+			self.zone_mut(location).unwrap().push(card_id);
+		}
+		self.library.sort();
+		self.sideboard.sort();
+	}
+
+	fn move_copies(&mut self, item: &CardListItem, from: CardLocation, to: CardLocation)
+		-> Result<(), SideboardError> {
+		let copies: Vec<CardId> = self.zone(from).unwrap().iter()
+			.filter(|card_id| self.cards[*card_id].name == item.0)
+			.take(item.1 as usize)
+			.cloned()
+			.collect();
+		if copies.len() < item.1 as usize {
+			return Err(SideboardError::NotEnoughCopies(item.0.clone()));
+		}
+		for card_id in copies {
+			self.move_card(card_id, from, to).unwrap();
+		}
+		Ok(())
+	}
+
 	/// gets an immutable reference to a card.
-	pub fn get_card_immut(&self, card_name: &String, card_key: u8) -> Option<Ref<RealCard<'a>>> {
-		let card_name = card_name.clone();
-		let card = self.cards.get(&(card_name, card_key))?.borrow();
-		Some(card)
+	pub fn get_card_immut(&self, card_id: CardId) -> Option<&RealCard> {
+		self.cards.get(&card_id)
 	}
 
 	/// gets a mutable reference to a card.
-	pub fn get_card(&self, card_name: &String, card_key:u8)-> Option<RefMut<RealCard<'a>>> {
-		let card_name = card_name.clone();
-		let card = self.cards.get(&(card_name, card_key))?.borrow_mut();
-		Some(card)
+	pub fn get_card(&mut self, card_id: CardId)-> Option<&mut RealCard> {
+		self.cards.get_mut(&card_id)
 	}
 
-	/// gets all cards of a given card name.
-	pub fn search_cards(&self, card_name: String) -> Vec<Ref<RealCard<'a>>> {
-		let card0 = self.get_card_immut(&card_name, 0).unwrap();
-		let mut vec = vec![];
-		for i in (0..card0.quantity){
-			vec.push(self.get_card_immut(&card_name, i).unwrap());
-		}
-		println!("{:#?}", vec);
+	/// gets all cards of a given card name (that aren't in the sideboard), in the order they were
+	/// added to the deck.
+	pub fn search_cards(&self, card_name: String) -> Vec<&RealCard> {
+		let mut vec: Vec<&RealCard> = self.cards.values()
+			.filter(|card| card.name == card_name)
+			.filter(|card| card.visibility_behavior.current_location != CardLocation::Sideboard)
+			.collect();
+		vec.sort_by_key(|card| card.id);
 		vec
 	}
 
-	/// shuffles the library
-	pub fn shuffle_library(&mut self)->(){
-		let mut rng = thread_rng();
-		&self.library.shuffle(&mut rng);
+	/// creates a token on the battlefield with the id it's given. Tokens go in `cards` like
+	/// everything else so they can be looked up.
+	pub fn create_token(&mut self, definition: &TokenDefinition, id: CardId) {
+		let mut token = RealCard::new_token(get_token_card(definition));
+		token.id = id;
+		self.cards.insert(id, token);
+		self.battlefield.push(id);
+	}
+
+	/// gives every card in the deck a new id from `ids`, keeping them in the same order. Games do
+	/// this to decks when they're added, so a card's id only depends on what's happened in the game.
+	pub fn renumber_cards(&mut self, ids: &mut CardIdAllocator) {
+		let mut old_ids: Vec<CardId> = self.cards.keys().cloned().collect();
+		old_ids.sort();
+		let new_ids: HashMap<CardId, CardId> = old_ids.into_iter().map(|id| (id, ids.next_id())).collect();
+		let renumber = |id: &mut CardId| *id = new_ids[id];
+		self.cards = self.cards.drain().map(|(id, mut card)| {
+			card.id = new_ids[&id];
+			(card.id, card)
+		}).collect();
+		for zone in [&mut self.library, &mut self.sideboard, &mut self.hand, &mut self.registered_sideboard,
+			&mut self.graveyard, &mut self.exile, &mut self.battlefield, &mut self.command, &mut self.ante,
+			&mut self.stack, &mut self.commanders] {
+			zone.iter_mut().for_each(renumber);
+		}
+		self.commander_casts = self.commander_casts.drain().map(|(id, casts)| (new_ids[&id], casts)).collect();
+		if let Some(companion) = &mut self.companion {
+			renumber(companion);
+		}
+		if let Some(look) = &mut self.pending_look {
+			look.card_ids.iter_mut().for_each(renumber);
+		}
+	}
+
+	/// tokens that aren't on the battlefield cease to exist (this is a state-based action, so it
+	/// should get called whenever those get checked). Returns how many tokens were removed.
+	pub fn remove_ceased_tokens(&mut self) -> usize {
+		let ceased: Vec<CardId> = self.cards.values()
+			.filter(|card| {
+				card.is_token && card.visibility_behavior.current_location != CardLocation::Battlefield
+			})
+			.map(|card| card.id)
+			.collect();
+
+		for card_id in ceased.iter() {
+			let token = self.cards.remove(card_id).unwrap();
+			if let Some(zone) = self.zone_mut(token.visibility_behavior.current_location) {
+				zone.retain(|id| id != card_id);
+			}
+		}
+		ceased.len()
+	}
+
+	/// puts a card onto the battlefield face down; this is what happens when a morph or disguise
+	/// spell resolves. The event only says who did it, so opponents don't find out what the card is.
+	pub fn put_onto_battlefield_face_down(&mut self, card_id: CardId, kind: FaceDownKind)
+		-> Option<CurrentEvent> {
+		self.move_card_from_anywhere(card_id, CardLocation::Battlefield).ok()?;
+		self.get_card(card_id)?.turn_face_down(kind);
+		Some(CurrentEvent::CardPlacedFaceDown(EventSource::Player(self.player.clone())))
+	}
+
+	/// manifests (or cloaks) the top card of the library.
+	pub fn manifest_top_card(&mut self, kind: FaceDownKind) -> Option<CurrentEvent> {
+		let card_id = *self.library.last()?;
+		self.put_onto_battlefield_face_down(card_id, kind)
+	}
+
+	/// turns a face-down permanent face up. Returns what it costs to do that along with the event.
+	pub fn turn_face_up(&mut self, card_id: CardId) -> Result<(Cost, CurrentEvent), FaceDownError> {
+		let card = self.get_card(card_id).ok_or(FaceDownError::NotFaceDown)?;
+		if card.visibility_behavior.current_location != CardLocation::Battlefield {
+			return Err(FaceDownError::NotOnBattlefield);
+		}
+		let cost = card.turn_face_up()?;
+		let event = CurrentEvent::CardTurnedFaceUp(EventSource::Card(self.player.name.clone(), card_id));
+		Ok((cost, event))
+	}
+
+	/// puts every planeswalker with no loyalty into the graveyard (it's a state-based action).
+	/// returns the ids of the planeswalkers that got put there.
+	pub fn put_zero_loyalty_planeswalkers_into_graveyard(&mut self) -> Vec<CardId> {
+		let zero_loyalty: Vec<CardId> = self.battlefield.iter()
+			.filter(|id| self.cards[*id].has_zero_loyalty())
+			.cloned()
+			.collect();
+		for card_id in zero_loyalty.iter() {
+			self.move_card(*card_id, CardLocation::Battlefield, CardLocation::Graveyard).unwrap();
+		}
+		zero_loyalty
+	}
+
+	/// untaps every permanent this player has on the battlefield. Happens in their untap step.
+	pub fn untap_permanents(&mut self) {
+		for card_id in self.battlefield.iter() {
+			if let Some(card) = self.cards.get_mut(card_id) {
+				card.battlefield_behavior.set_tapped(false);
+			}
+		}
+	}
+
+	/// lets every planeswalker on the battlefield activate a loyalty ability again. Happens when
+	/// the player's turn starts.
+	pub fn reset_loyalty_abilities(&mut self) {
+		for card_id in self.battlefield.iter() {
+			if let Some(loyalty_behavior) = self.cards.get_mut(card_id).and_then(|card| card.loyalty_behavior.as_mut()) {
+				loyalty_behavior.reset_for_new_turn();
+			}
+		}
+	}
+
+	/// a permanent this player controls that says they win instead of drawing from an empty
+	/// library, like Laboratory Maniac.
+	pub fn wins_instead_of_drawing_from_empty_library(&self) -> Option<CardId> {
+		self.battlefield.iter()
+			.find(|card_id| says_you_win_instead_of_drawing(&self.cards[*card_id].card.description))
+			.cloned()
+	}
+
+	/// shuffles the library with the game's rng.
+	pub fn shuffle_library(&mut self, rng: &mut GameRng){
+		rng.shuffle(&mut self.library);
 	}
 
 	pub fn get_library_card_names_in_order(&self) -> Vec<&str>{
-		let names: Vec<&str> = self.library.iter().map(|card| card.borrow().name).collect();
+		let names: Vec<&str> = self.library.iter().map(|id| self.cards[id].name).collect();
 		names
 	}
 
-	/// draw a card from your deck's library to your hand.
-	pub fn draw_card(&mut self) -> Option<Rc<RefCell<RealCard<'a>>>> {
-		let card_opt = self.send_card_from_library_to_place(CardLocation::Hand);
-		match card_opt {
-			Some(card_opt) => Some(card_opt),
-			None => None
+	/// draw a card from your deck's library to your hand. Returns the card (None if the library's
+	/// empty) and the CardDrawn event for it.
+	pub fn draw_card(&mut self) -> (Option<CardId>, Vec<CurrentEvent>) {
+		match self.send_card_from_library_to_place(CardLocation::Hand) {
+			Some(card_id) => (Some(card_id), vec![CurrentEvent::CardDrawn(self.event_source(card_id))]),
+			None => (None, vec![])
+		}
+	}
+
+	/// puts the top card of the library into the graveyard. Returns the card (None if the library's
+	/// empty) and the event for where it ended up.
+	pub fn mill_card(&mut self) -> (Option<CardId>, Vec<CurrentEvent>) {
+		match self.send_card_from_library_to_place(CardLocation::Graveyard) {
+			Some(card_id) => {
+				let to = self.location_of(card_id).unwrap();
+				(Some(card_id), placed_event(to, self.event_source(card_id)).into_iter().collect())
+			},
+			None => (None, vec![])
 		}
 	}
 
-	pub fn mill_card(&mut self) -> Option<Rc<RefCell<RealCard<'a>>>> {
-		self.send_card_from_library_to_place(CardLocation::Graveyard)
+	/// puts cards into the graveyard from wherever they are, in the order given, so the last one
+	/// ends up on top. If any of them isn't in this deck, nothing moves.
+	pub fn put_into_graveyard(&mut self, card_ids: &[CardId]) -> Result<Vec<CurrentEvent>, ZoneError> {
+		if card_ids.iter().any(|card_id| !self.cards.contains_key(card_id)) {
+			return Err(ZoneError::CardNotFound);
+		}
+		let mut events = vec![];
+		for card_id in card_ids.iter() {
+			let from = self.location_of(*card_id).unwrap();
+			events.extend(self.move_card_with_events(*card_id, from, CardLocation::Graveyard)?);
+		}
+		Ok(events)
 	}
 
-	/// todo add method for scrying/surveiling a card
-	// this is hard because we have to prompt the user for input...aka wait for them to finish
-	// looking at it before we put it back on library.
-	// fn scry_card(&mut self) ->
+	/// returns a card from the graveyard or exile to the hand, the battlefield or the top of the
+	/// library.
+	pub fn return_card(&mut self, card_id: CardId, from: CardLocation, to: CardLocation)
+		-> Result<Vec<CurrentEvent>, ZoneError> {
+		let from_allowed = matches!(from, CardLocation::Graveyard | CardLocation::Exile);
+		let to_allowed = matches!(to, CardLocation::Hand | CardLocation::Battlefield | CardLocation::Library);
+		if !from_allowed || !to_allowed {
+			return Err(ZoneError::NotAllowed);
+		}
+		self.move_card_with_events(card_id, from, to)
+	}
 
-	/// todo add method for revealing a card on top of library
-	pub fn reveal_top_card(&mut self, should_reveal: bool) -> Option<()>{
-		let card = match self.library.pop(){
-			Some(card) => card,
-			None => return None
+	/// exiles every card in the graveyard, keeping their order.
+	pub fn exile_graveyard(&mut self) -> Vec<CurrentEvent> {
+		let mut events = vec![CurrentEvent::GraveyardExiled(EventSource::Player(self.player.clone()))];
+		for card_id in self.graveyard.clone() {
+			self.move_card(card_id, CardLocation::Graveyard, CardLocation::Exile).unwrap();
+			events.push(CurrentEvent::CardPlacedInExile(self.event_source(card_id)));
+		}
+		events
+	}
+
+	/// shuffles the graveyard into the library, like Elixir of Immortality.
+	pub fn shuffle_graveyard_into_library(&mut self, rng: &mut GameRng) -> Vec<CurrentEvent> {
+		for card_id in self.graveyard.clone() {
+			self.move_card(card_id, CardLocation::Graveyard, CardLocation::Library).unwrap();
+		}
+		self.shuffle_library(rng);
+		let source = EventSource::Player(self.player.clone());
+		vec![CurrentEvent::GraveyardShuffledIntoLibrary(source.clone()), CurrentEvent::LibraryShuffled(source)]
+	}
+
+	/// move_card, plus the events for the card leaving its old zone and getting to the new one
+	/// (which might be the command zone, for a commander).
+	pub fn move_card_with_events(&mut self, card_id: CardId, from: CardLocation, to: CardLocation)
+		-> Result<Vec<CurrentEvent>, ZoneError> {
+		self.move_card(card_id, from, to)?;
+		let to = self.location_of(card_id).unwrap();
+		let source = self.event_source(card_id);
+		Ok(removed_event(from, source.clone()).into_iter().chain(placed_event(to, source)).collect())
+	}
+
+	/// starts looking at the top `n` cards of the library (fewer if there aren't that many) for a
+	/// scry, surveil, etc. Returns them top card first. Nothing moves until the player decides
+	/// with scry, surveil or put_back_on_top.
+	pub fn look_at_top(&mut self, n: usize, kind: LookKind) -> Vec<CardId> {
+		let card_ids: Vec<CardId> = self.library.iter().rev().take(n).cloned().collect();
+		self.pending_look = Some(PendingLook { kind, card_ids: card_ids.clone() });
+		card_ids
+	}
+
+	/// finishes a scry. `top` and `bottom` are both in order from top to bottom, so the first card
+	/// in `top` ends up on top of the library and the last card in `bottom` ends up on the bottom.
+	pub fn scry(&mut self, top: &[CardId], bottom: &[CardId]) -> Result<Vec<CurrentEvent>, LibraryError> {
+		self.take_pending_look(&[LookKind::Scry], &[top, bottom])?;
+		Ok(self.put_in_library(top, bottom))
+	}
+
+	/// finishes a surveil. `top` is in order from top to bottom.
+	pub fn surveil(&mut self, top: &[CardId], graveyard: &[CardId]) -> Result<Vec<CurrentEvent>, LibraryError> {
+		self.take_pending_look(&[LookKind::Surveil], &[top, graveyard])?;
+		let mut events = self.put_in_library(top, &[]);
+		for card_id in graveyard.iter() {
+			self.move_card(*card_id, CardLocation::Library, CardLocation::Graveyard).unwrap();
+			events.push(CurrentEvent::CardPlacedInGraveyard(self.event_source(*card_id)));
+		}
+		Ok(events)
+	}
+
+	/// finishes just looking at the cards; `top` is the order they go back in, top to bottom.
+	pub fn put_back_on_top(&mut self, top: &[CardId]) -> Result<Vec<CurrentEvent>, LibraryError> {
+		self.take_pending_look(&[LookKind::Look, LookKind::Scry, LookKind::Surveil], &[top])?;
+		Ok(self.put_in_library(top, &[]))
+	}
+
+	/// puts cards from the library on the bottom in a random order, like after a cascade or
+	/// "reveal until".
+	pub fn put_on_bottom_in_random_order(&mut self, card_ids: &[CardId], rng: &mut GameRng) -> Vec<CurrentEvent> {
+		let mut bottom: Vec<CardId> = card_ids.iter().filter(|id| self.library.contains(id)).cloned().collect();
+		rng.shuffle(&mut bottom);
+		self.put_in_library(&[], &bottom)
+	}
+
+	/// reveals cards from the top of the library until one matches. Returns the one that matched
+	/// (None if it never did) and every other card revealed, top card first. The cards stay on top
+	/// of the library, revealed, for whatever the effect does with them next.
+	pub fn reveal_until<F: Fn(&RealCard) -> bool>(&mut self, matches: F)
+		-> (Option<CardId>, Vec<CardId>, Vec<CurrentEvent>) {
+		let mut revealed = vec![];
+		let mut events = vec![];
+		let mut found = None;
+		for card_id in self.library.clone().iter().rev() {
+			self.cards.get_mut(card_id).unwrap().visibility_behavior.set_revealed(true);
+			events.push(CurrentEvent::CardRevealedOnLibrary(self.event_source(*card_id)));
+			if matches(&self.cards[card_id]) {
+				found = Some(*card_id);
+				break;
+			}
+			revealed.push(*card_id);
+		}
+		(found, revealed, events)
+	}
+
+	/// starts searching the library; returns every card that matches. The player picks which
+	/// ones they actually take with finish_search.
+	pub fn search_library<F: Fn(&RealCard) -> bool>(&mut self, matches: F) -> Vec<CardId> {
+		let card_ids: Vec<CardId> = self.library.iter().rev()
+			.filter(|card_id| matches(&self.cards[*card_id]))
+			.cloned()
+			.collect();
+		self.pending_look = Some(PendingLook { kind: LookKind::Search, card_ids: card_ids.clone() });
+		card_ids
+	}
+
+	/// takes the chosen cards out of the library, puts them in `to`, and shuffles the library.
+	/// (You're allowed to not find anything, so `chosen` can be empty.) Searched-for cards go to
+	/// the hand, the battlefield, the graveyard or exile.
+	pub fn finish_search(&mut self, chosen: &[CardId], to: CardLocation, rng: &mut GameRng)
+		-> Result<Vec<CurrentEvent>, LibraryError> {
+		match &self.pending_look {
+			Some(look) if look.kind == LookKind::Search => {
+				if !chosen.iter().all(|card_id| look.card_ids.contains(card_id)) {
+					return Err(LibraryError::WrongCards);
+				}
+			},
+			_ => return Err(LibraryError::NothingPending),
+		}
+		if chosen.iter().enumerate().any(|(i, card_id)| chosen[..i].contains(card_id)) {
+			return Err(LibraryError::SameCardTwice);
+		}
+		if !matches!(to, CardLocation::Hand | CardLocation::Battlefield | CardLocation::Graveyard | CardLocation::Exile) {
+			return Err(LibraryError::NotAllowed);
+		}
+		self.pending_look = None;
+		let mut events = vec![];
+		for card_id in chosen.iter() {
+			self.move_card(*card_id, CardLocation::Library, to).map_err(LibraryError::Zone)?;
+			if let Some(event) = placed_event(to, self.event_source(*card_id)) {
+				events.push(event);
+			}
+		}
+		self.shuffle_library(rng);
+		events.push(CurrentEvent::LibraryShuffled(EventSource::Player(self.player.clone())));
+		Ok(events)
+	}
+
+	/// makes sure the pending look is one of `kinds` and that `chosen` split up its cards exactly,
+	/// then clears it.
+	fn take_pending_look(&mut self, kinds: &[LookKind], chosen: &[&[CardId]]) -> Result<(), LibraryError> {
+		let look = match &self.pending_look {
+			Some(look) => look,
+			None => return Err(LibraryError::NothingPending),
 		};
-		card.borrow_mut().visibility_behavior.set_revealed(should_reveal);
-		self.library.push(card);
+		if !kinds.contains(&look.kind) {
+			return Err(LibraryError::NotAllowed);
+		}
+		let mut chosen: Vec<CardId> = chosen.concat();
+		let mut expected = look.card_ids.clone();
+		chosen.sort();
+		expected.sort();
+		if chosen != expected {
+			return Err(LibraryError::WrongCards);
+		}
+		self.pending_look = None;
+		Ok(())
+	}
+
+	/// takes the cards out of the library and puts `top` back on top and `bottom` on the bottom
+	/// (both in order from top to bottom).
+	fn put_in_library(&mut self, top: &[CardId], bottom: &[CardId]) -> Vec<CurrentEvent> {
+		self.library.retain(|card_id| !top.contains(card_id) && !bottom.contains(card_id));
+		let mut library: Vec<CardId> = bottom.iter().rev().cloned().collect();
+		library.append(&mut self.library);
+		library.extend(top.iter().rev());
+		self.library = library;
+		top.iter().map(|card_id| CurrentEvent::CardPutOnTopOfLibrary(self.event_source(*card_id)))
+			.chain(bottom.iter().map(|card_id| CurrentEvent::CardPutOnBottomOfLibrary(self.event_source(*card_id))))
+			.collect()
+	}
+
+	fn event_source(&self, card_id: CardId) -> EventSource {
+		EventSource::Card(self.player.name.clone(), card_id)
+	}
+
+	/// shuffles the library and draws an opening hand.
+	pub fn draw_opening_hand(&mut self, rng: &mut GameRng) {
+		self.shuffle_library(rng);
+		for _ in 0..OPENING_HAND_SIZE {
+			self.draw_card();
+		}
+	}
+
+	/// shuffles the hand back into the library and draws a new one. (Which cards go on the bottom
+	/// afterwards is up to the player; see put_on_bottom.)
+	pub fn mulligan(&mut self, rng: &mut GameRng) {
+		for card_id in self.hand.clone() {
+			self.move_card(card_id, CardLocation::Hand, CardLocation::Library).unwrap();
+		}
+		self.draw_opening_hand(rng);
+	}
+
+	/// puts cards on the bottom of the library from wherever they are, in order from top to bottom.
+	/// Nothing moves unless every card is in the deck and none of them are picked twice. A
+	/// commander that goes to the command zone instead doesn't end up on the bottom.
+	pub fn put_on_bottom(&mut self, card_ids: &[CardId]) -> Result<Vec<CurrentEvent>, ZoneError> {
+		for (i, card_id) in card_ids.iter().enumerate() {
+			if card_ids[..i].contains(card_id) {
+				return Err(ZoneError::SameCardTwice);
+			}
+			self.location_of(*card_id).ok_or(ZoneError::CardNotFound)?;
+		}
+		let mut moved = vec![];
+		for card_id in card_ids.iter() {
+			self.move_card_from_anywhere(*card_id, CardLocation::Library)?;
+			if self.location_of(*card_id) == Some(CardLocation::Library) {
+				moved.push(*card_id);
+			}
+		}
+		Ok(self.put_in_library(&[], &moved))
+	}
+
+	/// reveals (or un-reveals) the top card of the library.
+	pub fn reveal_top_card(&mut self, should_reveal: bool) -> Option<()>{
+		let card_id = self.library.last()?;
+		self.cards.get_mut(card_id)?.visibility_behavior.set_revealed(should_reveal);
 		Some(())
 	}
 
 	fn send_card_from_library_to_place(&mut self, place: CardLocation)
-		-> Option<Rc<RefCell<RealCard<'a>>>> {
-		let card = match self.library.pop(){
-			Some(card) => card,
-			None => return None
-		};
-		card.borrow_mut().visibility_behavior.set_location(place);
-		Some(card)
+		-> Option<CardId> {
+		let card_id = *self.library.last()?;
+		self.move_card(card_id, CardLocation::Library, place).ok()?;
+		Some(card_id)
+	}
+}
+
+/// whether some rules text says you win instead of drawing from an empty library, like Laboratory
+/// Maniac and Jace, Wielder of Mysteries. Both halves just have to be in the same sentence, so older
+/// wordings ("while you have no cards in your library") count too.
+pub fn says_you_win_instead_of_drawing(description: &str) -> bool {
+	static DRAWING_FROM_EMPTY: OnceLock<Regex> = OnceLock::new();
+	static WIN_INSTEAD: OnceLock<Regex> = OnceLock::new();
+	let drawing_from_empty = DRAWING_FROM_EMPTY.get_or_init(|| Regex::new(
+		r"(?i)would draw a card while (?:your library has no cards in it|you have no cards in your library|your library is empty)"
+	).unwrap());
+	let win_instead = WIN_INSTEAD.get_or_init(|| Regex::new(r"(?i)you win the game instead").unwrap());
+	description.split(['.', '\n'])
+		.any(|sentence| drawing_from_empty.is_match(sentence) && win_instead.is_match(sentence))
+}
+
+/// the event for a card getting put somewhere, if there is one.
+fn placed_event(location: CardLocation, source: EventSource) -> Option<CurrentEvent> {
+	match location {
+		CardLocation::Battlefield => Some(CurrentEvent::CardPlacedOnBattlefield(source)),
+		CardLocation::Exile => Some(CurrentEvent::CardPlacedInExile(source)),
+		CardLocation::Graveyard => Some(CurrentEvent::CardPlacedInGraveyard(source)),
+		CardLocation::Hand => Some(CurrentEvent::CardPutIntoHand(source)),
+		CardLocation::Library => Some(CurrentEvent::CardPutOnTopOfLibrary(source)),
+		_ => None
+	}
+}
+
+fn removed_event(location: CardLocation, source: EventSource) -> Option<CurrentEvent> {
+	match location {
+		CardLocation::Battlefield => Some(CurrentEvent::CardRemovedFromBattlefield(source)),
+		CardLocation::Exile => Some(CurrentEvent::CardRemovedFromExile(source)),
+		CardLocation::Graveyard => Some(CurrentEvent::CardRemovedFromGraveyard(source)),
+		_ => None
 	}
 }
 
@@ -158,10 +841,8 @@ mod tests {
 	use super::*;
 
 	#[test]
-	/// I think that TECHNICALLY this function has the capability of failing randomly.
-	///  because if you truely randomly shuffle something, it's POSSIBLE that it shuffles
-	/// into the same order as before you shuffled. So if this test fails once in a while...
-	/// rerun the test.
+	/// the rng is seeded, so this always shuffles the same way (and we know that way isn't the
+	/// order the cards started in).
 	fn library_shuffle(){
 		let vec = vec![
 			CardListItem("Mind's Eye".to_string(), 1),
@@ -176,19 +857,23 @@ mod tests {
 			CardListItem("Opt".to_string(), 1),
 		];
 		let vec_b = vec![];
-		let mut lib = Deck::new(Player{name: "Me".to_string() } ,&vec, &vec_b).unwrap();
-		let before0 = lib.library[0].borrow().name;
-		let before1 = lib.library[1].borrow().name;
-		let before2 = lib.library[2].borrow().name;
+		let mut lib = Deck::new(Player::new("Me") ,&vec, &vec_b).unwrap();
+		let before0 = lib.library[0];
+		let before1 = lib.library[1];
+		let before2 = lib.library[2];
 
-		lib.shuffle_library();
+		lib.shuffle_library(&mut GameRng::new(42));
 
-		let after0 = lib.library[0].borrow().name;
-		let after1 = lib.library[1].borrow().name;
-		let after2 = lib.library[2].borrow().name;
+		let after0 = lib.library[0];
+		let after1 = lib.library[1];
+		let after2 = lib.library[2];
 
 		assert_ne!([before0, before1, before2], [after0, after1, after2]);
 
+		let mut lib2 = Deck::new(Player::new("Me") ,&vec, &vec_b).unwrap();
+		lib2.shuffle_library(&mut GameRng::new(42));
+		assert_eq!(lib.get_library_card_names_in_order(), lib2.get_library_card_names_in_order());
+
 	}
 
 	#[test]
@@ -197,13 +882,14 @@ mod tests {
 			CardListItem("Mind's Eye".to_string(), 3)
 		];
 		let vec_b= vec![];
-		let lib = Deck::new(Player{name: "Me".to_string() }, &vec, &vec_b).unwrap();
-		let me0 = &lib.cards[&("Mind's Eye".to_string(), 0)];
-		let me1 = &lib.cards[&("Mind's Eye".to_string(), 1)];
-		let me2 = &lib.cards[&("Mind's Eye".to_string(), 2)];
-		assert_eq!(me0.borrow().name, "Mind's Eye".to_string());
-		assert_eq!(me1.borrow().name, "Mind's Eye".to_string());
-		assert_eq!(me2.borrow().name, "Mind's Eye".to_string());
+		let lib = Deck::new(Player::new("Me"), &vec, &vec_b).unwrap();
+		let me0 = &lib.cards[&lib.library[0]];
+		let me1 = &lib.cards[&lib.library[1]];
+		let me2 = &lib.cards[&lib.library[2]];
+		assert_eq!(me0.name, "Mind's Eye".to_string());
+		assert_eq!(me1.name, "Mind's Eye".to_string());
+		assert_eq!(me2.name, "Mind's Eye".to_string());
+		assert_eq!(lib.cards.len(), 3);
 		assert_eq!(lib.library.len(), 3);
 	}
 
@@ -213,7 +899,7 @@ mod tests {
 			CardListItem("Mind's Eye".to_string(), 3)
 		];
 		let vec_b= vec![];
-		let lib = Deck::new(Player{name: "Me".to_string() }, &vec, &vec_b).unwrap();
+		let lib = Deck::new(Player::new("Me"), &vec, &vec_b).unwrap();
 		let result = lib.search_cards("Mind's Eye".to_string());
 		assert_eq!(result.len(), 3);
 	}
@@ -224,12 +910,451 @@ mod tests {
 			CardListItem("Mind's Eye".to_string(), 3)
 		];
 		let vec_b = vec![];
-		let lib = Deck::new(Player{name: "Me".to_string() }, &vec, &vec_b).unwrap();
-		let mut card = lib.get_card(&"Mind's Eye".to_string(), 0).unwrap();
+		let mut lib = Deck::new(Player::new("Me"), &vec, &vec_b).unwrap();
+		let (id0, id1) = (lib.library[0], lib.library[1]);
+		let card = lib.get_card(id0).unwrap();
 		card.change_current_location(CardLocation::Graveyard);
-		let card2 = lib.get_card_immut(&"Mind's Eye".to_string(), 1).unwrap();
+		let card = lib.get_card_immut(id0).unwrap();
+		let card2 = lib.get_card_immut(id1).unwrap();
 		println!("{:#?}\n\n\n{:#?}", card, card2);
 		assert_ne!(card.visibility_behavior.current_location, card2.visibility_behavior.current_location);
 
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn move_card_takes_the_card_out_of_its_old_zone(){
+		let vec = vec![
+			CardListItem("Mind's Eye".to_string(), 3)
+		];
+		let vec_b = vec![];
+		let mut lib = Deck::new(Player::new("Me"), &vec, &vec_b).unwrap();
+		let id = lib.library[1];
+		lib.move_card(id, CardLocation::Library, CardLocation::Battlefield).unwrap();
+		assert_eq!(lib.library.len(), 2);
+		assert_eq!(lib.battlefield.len(), 1);
+		assert_eq!(lib.battlefield[0], id);
+
+		lib.move_card(id, CardLocation::Battlefield, CardLocation::Command).unwrap();
+		assert_eq!(lib.battlefield.len(), 0);
+		assert_eq!(lib.command.len(), 1);
+		assert_eq!(lib.get_card_immut(id).unwrap().visibility_behavior.zone_change_count(), 2);
+	}
+
+	#[test]
+	fn planeswalkers_with_no_loyalty_go_to_the_graveyard(){
+		let vec = vec![
+			CardListItem("Elspeth Tirel".to_string(), 1)
+		];
+		let vec_b = vec![];
+		let mut lib = Deck::new(Player::new("Me"), &vec, &vec_b).unwrap();
+		let id = lib.library[0];
+		lib.move_card(id, CardLocation::Library, CardLocation::Battlefield).unwrap();
+		{
+			let elspeth = lib.get_card(id).unwrap();
+			assert_eq!(elspeth.counters.get(&crate::counters::CounterType::Loyalty), 4);
+			elspeth.activate_loyalty_ability(1, 0, true).unwrap();
+			elspeth.take_damage(1);
+			assert_eq!(elspeth.counters.get(&crate::counters::CounterType::Loyalty), 1);
+		}
+		assert!(lib.put_zero_loyalty_planeswalkers_into_graveyard().is_empty());
+		lib.get_card(id).unwrap().take_damage(3);
+		assert_eq!(lib.put_zero_loyalty_planeswalkers_into_graveyard().len(), 1);
+		assert_eq!(lib.graveyard.len(), 1);
+	}
+
+	#[test]
+	fn face_down_cards_are_hidden_from_opponents(){
+		let vec = vec![
+			CardListItem("Fugitive Codebreaker".to_string(), 1),
+			CardListItem("Murder".to_string(), 1),
+		];
+		let vec_b = vec![];
+		let mut lib = Deck::new(Player::new("Me"), &vec, &vec_b).unwrap();
+		let id = lib.search_cards("Fugitive Codebreaker".to_string())[0].id;
+		let event = lib.put_onto_battlefield_face_down(id, FaceDownKind::Disguise).unwrap();
+		assert_eq!(event, CurrentEvent::CardPlacedFaceDown(EventSource::Player(Player::new("Me"))));
+		{
+			let codebreaker = lib.get_card_immut(id).unwrap();
+			let opponent_view = codebreaker.view(false);
+			assert_eq!(opponent_view.name, None);
+			assert_eq!((opponent_view.power, opponent_view.toughness), (2, 2));
+			assert_eq!(opponent_view.keywords, vec!["Ward 2".to_string()]);
+			assert_eq!(codebreaker.view(true).name, Some("Fugitive Codebreaker".to_string()));
+		}
+
+		let (cost, event) = lib.turn_face_up(id).unwrap();
+		assert_eq!(cost.cost.get(&crate::colors::Color::C), Some(&5));
+		assert_eq!(event, CurrentEvent::CardTurnedFaceUp(EventSource::Card("Me".to_string(), id)));
+		assert_eq!(lib.get_card_immut(id).unwrap().view(false).power, 2);
+	}
+
+	#[test]
+	fn manifested_noncreatures_cant_be_turned_face_up(){
+		let vec = vec![
+			CardListItem("Murder".to_string(), 1),
+		];
+		let vec_b = vec![];
+		let mut lib = Deck::new(Player::new("Me"), &vec, &vec_b).unwrap();
+		let id = lib.library[0];
+		lib.manifest_top_card(FaceDownKind::Manifest).unwrap();
+		assert_eq!(lib.library.len(), 0);
+		assert!(lib.get_card_immut(id).unwrap().battlefield_behavior.is_face_down());
+		assert_eq!(lib.turn_face_up(id).unwrap_err(), FaceDownError::CantBeTurnedFaceUp);
+	}
+
+	#[test]
+	fn only_face_down_permanents_get_turned_face_up(){
+		let vec = vec![CardListItem("Fugitive Codebreaker".to_string(), 1)];
+		let mut lib = Deck::new(Player::new("Me"), &vec, &vec![]).unwrap();
+		let id = lib.library[0];
+		lib.move_card(id, CardLocation::Library, CardLocation::Stack).unwrap();
+		lib.get_card(id).unwrap().turn_face_down(FaceDownKind::Disguise);
+		assert_eq!(lib.turn_face_up(id).unwrap_err(), FaceDownError::NotOnBattlefield);
+		assert!(lib.get_card_immut(id).unwrap().battlefield_behavior.is_face_down());
+	}
+
+	#[test]
+	fn tokens_cease_to_exist_when_they_leave_the_battlefield(){
+		let vec = vec![
+			CardListItem("Siege-Gang Commander".to_string(), 1)
+		];
+		let vec_b = vec![];
+		let mut lib = Deck::new(Player::new("Me"), &vec, &vec_b).unwrap();
+		let creation = crate::token::parse_token_creations(&lib.search_cards("Siege-Gang Commander".to_string())[0].card.description).remove(0);
+		let goblins: Vec<CardId> = (0..3).map(|_| CardId::generate()).collect();
+		for goblin in goblins.iter() {
+			lib.create_token(&creation.definition, *goblin);
+		}
+		assert_eq!(lib.battlefield.len(), 3);
+		assert!(lib.get_card_immut(goblins[2]).unwrap().is_token);
+
+		lib.move_card(goblins[1], CardLocation::Battlefield, CardLocation::Graveyard).unwrap();
+		assert_eq!(lib.graveyard.len(), 1);
+		assert_eq!(lib.remove_ceased_tokens(), 1);
+		assert_eq!(lib.graveyard.len(), 0);
+		assert_eq!(lib.battlefield.len(), 2);
+		assert!(lib.get_card_immut(goblins[1]).is_none());
+	}
+
+	#[test]
+	fn every_card_gets_its_own_id_even_across_decks(){
+		let vec = vec![
+			CardListItem("Forest".to_string(), 300)
+		];
+		let vec_b = vec![
+			CardListItem("Forest".to_string(), 2)
+		];
+		let mine = Deck::new(Player::new("Me"), &vec, &vec_b).unwrap();
+		let theirs = Deck::new(Player::new("You"), &vec, &vec_b).unwrap();
+		assert_eq!(mine.library.len(), 300);
+		assert_eq!(mine.sideboard.len(), 2);
+		let mut ids: Vec<CardId> = mine.cards.keys().chain(theirs.cards.keys()).cloned().collect();
+		ids.sort();
+		ids.dedup();
+		assert_eq!(ids.len(), 604);
+	}
+
+	#[test]
+	fn commanders_live_in_the_command_zone_and_get_taxed(){
+		let vec = vec![
+			CardListItem("Neyali, Suns' Vanguard".to_string(), 1),
+			CardListItem("Plains".to_string(), 1),
+		];
+		let vec_b = vec![];
+		let mut lib = Deck::new(Player::new("Me"), &vec, &vec_b).unwrap();
+		let neyali = lib.search_cards("Neyali, Suns' Vanguard".to_string())[0].id;
+		let plains = lib.search_cards("Plains".to_string())[0].id;
+		assert_eq!(lib.designate_commander(plains), Err(CommanderError::CantBeCommander("Plains".to_string())));
+		lib.designate_commander(neyali).unwrap();
+		assert_eq!(lib.command, vec![neyali]);
+		assert_eq!(lib.library, vec![plains]);
+
+		let cost = lib.cast_commander(neyali).unwrap();
+		assert_eq!(cost.cost.get(&crate::colors::Color::C), Some(&2));
+		assert_eq!(lib.cast_commander(neyali), Err(CommanderError::NotInCommandZone));
+		lib.move_card(neyali, CardLocation::Stack, CardLocation::Battlefield).unwrap();
+		lib.move_card(neyali, CardLocation::Battlefield, CardLocation::Graveyard).unwrap();
+		assert!(lib.graveyard.is_empty());
+		assert_eq!(lib.commander_tax(neyali), 2);
+		let cost = lib.cast_commander(neyali).unwrap();
+		assert_eq!(cost.cost.get(&crate::colors::Color::C), Some(&4));
+
+		lib.commanders_return_to_command_zone = false;
+		lib.move_card(neyali, CardLocation::Stack, CardLocation::Exile).unwrap();
+		assert_eq!(lib.exile, vec![neyali]);
+	}
+
+	#[test]
+	fn sideboarding_keeps_the_deck_legal_and_can_be_undone(){
+		use crate::deck_validation::Format;
+		let vec = vec![
+			CardListItem("Lightning Bolt".to_string(), 4),
+			CardListItem("Mountain".to_string(), 56),
+		];
+		let vec_b = vec![
+			CardListItem("Soldier of Fortune".to_string(), 4),
+			CardListItem("Dragon's Rage Channeler".to_string(), 2),
+		];
+		let mut lib = Deck::new(Player::new("Me"), &vec, &vec_b).unwrap();
+		let validator = DeckValidator::new(Format::Modern);
+		let bolts = vec![CardListItem("Lightning Bolt".to_string(), 2)];
+		let soldiers = vec![CardListItem("Soldier of Fortune".to_string(), 2)];
+
+		assert!(matches!(
+			lib.swap_sideboard_cards(&bolts, &[], Some(&validator)),
+			Err(SideboardError::IllegalDeck(_))
+		));
+		assert_eq!(lib.library.len(), 60);
+		assert_eq!(
+			lib.swap_sideboard_cards(&[], &[CardListItem("Dragon's Rage Channeler".to_string(), 3)], None),
+			Err(SideboardError::NotEnoughCopies("Dragon's Rage Channeler".to_string()))
+		);
+
+		lib.swap_sideboard_cards(&bolts, &soldiers, Some(&validator)).unwrap();
+		assert_eq!(lib.search_cards("Lightning Bolt".to_string()).len(), 2);
+		assert_eq!(lib.search_cards("Soldier of Fortune".to_string()).len(), 2);
+		assert_eq!(lib.sideboard.len(), 6);
+
+		lib.draw_card();
+		lib.restore_registered_list();
+		assert_eq!(lib.search_cards("Lightning Bolt".to_string()).len(), 4);
+		assert_eq!(lib.library.len(), 60);
+		assert_eq!(lib.sideboard, lib.registered_sideboard);
+	}
+
+	fn ten_card_deck() -> Deck {
+		let vec = vec![
+			CardListItem("Mind's Eye".to_string(), 1),
+			CardListItem("Forest".to_string(), 1),
+			CardListItem("Swamp".to_string(), 1),
+			CardListItem("Insidious Roots".to_string(), 1),
+			CardListItem("Murder".to_string(), 1),
+			CardListItem("Lightning Storm".to_string(), 1),
+			CardListItem("Island".to_string(), 1),
+			CardListItem("Mountain".to_string(), 1),
+			CardListItem("Reject".to_string(), 1),
+			CardListItem("Opt".to_string(), 1),
+		];
+		Deck::new(Player::new("Me"), &vec, &vec![]).unwrap()
+	}
+
+	#[test]
+	fn scry_puts_cards_on_top_and_bottom_in_order(){
+		let mut lib = ten_card_deck();
+		let looked_at = lib.look_at_top(3, LookKind::Scry);
+		let names: Vec<&str> = looked_at.iter().map(|id| lib.cards[id].name).collect();
+		assert_eq!(names, vec!["Opt", "Reject", "Mountain"]);
+		assert_eq!(lib.scry(&looked_at[..1], &[]), Err(LibraryError::WrongCards));
+		assert_eq!(lib.surveil(&looked_at, &[]), Err(LibraryError::NotAllowed));
+
+		let events = lib.scry(&[looked_at[2], looked_at[0]], &[looked_at[1]]).unwrap();
+		assert_eq!(events.len(), 3);
+		assert_eq!(events[2], CurrentEvent::CardPutOnBottomOfLibrary(EventSource::Card("Me".to_string(), looked_at[1])));
+		let order = lib.get_library_card_names_in_order();
+		assert_eq!(order[0], "Reject");
+		assert_eq!(&order[8..], &["Opt", "Mountain"]);
+		assert_eq!(lib.pending_look, None);
+	}
+
+	#[test]
+	fn surveil_can_put_cards_in_the_graveyard(){
+		let mut lib = ten_card_deck();
+		let looked_at = lib.look_at_top(2, LookKind::Surveil);
+		lib.surveil(&[looked_at[1]], &[looked_at[0]]).unwrap();
+		assert_eq!(lib.graveyard, vec![looked_at[0]]);
+		assert_eq!(lib.library.len(), 9);
+		assert_eq!(*lib.library.last().unwrap(), looked_at[1]);
+	}
+
+	#[test]
+	fn reveal_until_stops_at_the_first_match(){
+		let mut lib = ten_card_deck();
+		let (found, revealed, events) = lib.reveal_until(|card| card.card.card_types.contains(&crate::card::CardType::Land));
+		assert_eq!(lib.cards[&found.unwrap()].name, "Mountain");
+		assert_eq!(revealed.len(), 2);
+		assert_eq!(events.len(), 3);
+		lib.put_on_bottom_in_random_order(&revealed, &mut GameRng::new(3));
+		assert!(revealed.contains(&lib.library[0]) && revealed.contains(&lib.library[1]));
+	}
+
+	#[test]
+	fn searching_takes_the_card_and_shuffles(){
+		let mut lib = ten_card_deck();
+		let swamps = lib.search_library(|card| card.name == "Swamp");
+		assert_eq!(swamps.len(), 1);
+		let events = lib.finish_search(&swamps, CardLocation::Battlefield, &mut GameRng::new(5)).unwrap();
+		assert_eq!(lib.battlefield, swamps);
+		assert_eq!(lib.library.len(), 9);
+		assert_eq!(events.last().unwrap(), &CurrentEvent::LibraryShuffled(EventSource::Player(Player::new("Me"))));
+		assert_eq!(lib.finish_search(&[], CardLocation::Hand, &mut GameRng::new(5)), Err(LibraryError::NothingPending));
+	}
+
+	#[test]
+	fn searches_cant_take_a_card_twice_or_put_it_nowhere(){
+		let mut lib = ten_card_deck();
+		let swamps = lib.search_library(|card| card.name == "Swamp");
+		let twice = [swamps[0], swamps[0]];
+		assert_eq!(lib.finish_search(&twice, CardLocation::Hand, &mut GameRng::new(5)), Err(LibraryError::SameCardTwice));
+		assert_eq!(lib.finish_search(&swamps, CardLocation::None, &mut GameRng::new(5)), Err(LibraryError::NotAllowed));
+		assert_eq!(lib.finish_search(&swamps, CardLocation::Stack, &mut GameRng::new(5)), Err(LibraryError::NotAllowed));
+		// nothing moved, and the search is still going.
+		assert_eq!(lib.library.len(), 10);
+		assert!(lib.pending_look.is_some());
+		lib.finish_search(&swamps, CardLocation::Hand, &mut GameRng::new(5)).unwrap();
+		assert_eq!(lib.hand, swamps);
+	}
+
+	#[test]
+	fn some_cards_say_you_win_instead_of_drawing(){
+		assert!(says_you_win_instead_of_drawing("If you would draw a card while your library has no cards in it, you win the game instead."));
+		assert!(says_you_win_instead_of_drawing("If you would draw a card while you have no cards in your library, you win the game instead."));
+		assert!(says_you_win_instead_of_drawing("You win the game instead if you would draw a card while your library has no cards in it."));
+		assert!(!says_you_win_instead_of_drawing("Draw a card. If your library has no cards in it, you win the game."));
+		assert!(!says_you_win_instead_of_drawing("If you would draw a card while your library has no cards in it. Then you win the game instead."));
+	}
+
+	#[test]
+	fn drawing_puts_cards_in_the_hand(){
+		let mut lib = ten_card_deck();
+		let (id, events) = lib.draw_card();
+		let id = id.unwrap();
+		assert_eq!(lib.hand, vec![id]);
+		assert_eq!(lib.library.len(), 9);
+		assert_eq!(events, vec![CurrentEvent::CardDrawn(lib.event_source(id))]);
+		let (id, events) = lib.mill_card();
+		let id = id.unwrap();
+		assert_eq!(lib.graveyard, vec![id]);
+		assert_eq!(events, vec![CurrentEvent::CardPlacedInGraveyard(lib.event_source(id))]);
+
+		let mut empty = Deck::default();
+		assert_eq!(empty.mill_card(), (None, vec![]));
+	}
+
+	#[test]
+	fn mulligans_shuffle_the_hand_back_in(){
+		let mut lib = ten_card_deck();
+		let mut rng = GameRng::new(11);
+		lib.draw_opening_hand(&mut rng);
+		assert_eq!((lib.hand.len(), lib.library.len()), (7, 3));
+		lib.mulligan(&mut rng);
+		assert_eq!((lib.hand.len(), lib.library.len()), (7, 3));
+		let bottom = vec![lib.hand[0], lib.hand[1]];
+		assert_eq!(lib.put_on_bottom(&[bottom[0], bottom[0]]), Err(ZoneError::SameCardTwice));
+		assert_eq!(lib.hand.len(), 7);
+		lib.put_on_bottom(&bottom).unwrap();
+		assert_eq!(lib.hand.len(), 5);
+		assert_eq!(&lib.library[..2], &[bottom[1], bottom[0]]);
+		assert!(lib.zones_are_consistent());
+	}
+
+	#[test]
+	fn move_card_only_moves_cards_from_where_they_are(){
+		let mut lib = ten_card_deck();
+		let id = lib.library[0];
+		assert_eq!(
+			lib.move_card(id, CardLocation::Hand, CardLocation::Battlefield),
+			Err(ZoneError::NotInZone { expected: CardLocation::Hand, actual: CardLocation::Library })
+		);
+		assert_eq!(lib.location_of(id), Some(CardLocation::Library));
+		assert_eq!(lib.move_card(ten_card_deck().library[0], CardLocation::Library, CardLocation::Hand), Err(ZoneError::CardNotFound));
+		assert_eq!(lib.move_card(id, CardLocation::Library, CardLocation::None), Err(ZoneError::NoSuchZone));
+		lib.move_card(id, CardLocation::Library, CardLocation::Stack).unwrap();
+		assert_eq!(lib.stack, vec![id]);
+		assert!(lib.zones_are_consistent());
+	}
+
+	#[test]
+	fn every_card_is_always_in_exactly_one_zone(){
+		let mut lib = ten_card_deck();
+		let mut rng = GameRng::new(3);
+		assert!(lib.zones_are_consistent());
+		lib.draw_opening_hand(&mut rng);
+		assert!(lib.zones_are_consistent());
+		lib.mulligan(&mut rng);
+		let bottom = vec![lib.hand[0]];
+		lib.put_on_bottom(&bottom).unwrap();
+		assert!(lib.zones_are_consistent());
+		let land = lib.hand[0];
+		lib.move_card(land, CardLocation::Hand, CardLocation::Battlefield).unwrap();
+		lib.move_card(land, CardLocation::Battlefield, CardLocation::Graveyard).unwrap();
+		lib.move_card(land, CardLocation::Graveyard, CardLocation::Exile).unwrap();
+		lib.draw_card();
+		lib.mill_card();
+		assert!(lib.zones_are_consistent());
+		let looked = lib.look_at_top(2, LookKind::Scry);
+		lib.scry(&looked[1..], &looked[..1]).unwrap();
+		assert!(lib.zones_are_consistent());
+		lib.restore_registered_list();
+		assert!(lib.zones_are_consistent());
+		assert_eq!(lib.library.len(), 10);
+
+		// a card that's in two zones at once (or in a zone it doesn't think it's in) gets caught.
+		let id = lib.library[0];
+		lib.hand.push(id);
+		assert!(!lib.zones_are_consistent());
+	}
+
+	#[test]
+	fn cards_go_to_the_graveyard_in_the_order_chosen(){
+		let mut lib = ten_card_deck();
+		let first = lib.library[0];
+		let second = lib.library[1];
+		lib.move_card(second, CardLocation::Library, CardLocation::Battlefield).unwrap();
+		let events = lib.put_into_graveyard(&[first, second]).unwrap();
+		assert_eq!(lib.graveyard, vec![first, second]);
+		assert_eq!(events, vec![
+			CurrentEvent::CardPlacedInGraveyard(lib.event_source(first)),
+			CurrentEvent::CardRemovedFromBattlefield(lib.event_source(second)),
+			CurrentEvent::CardPlacedInGraveyard(lib.event_source(second)),
+		]);
+		let stranger = ten_card_deck().library[0];
+		assert_eq!(lib.put_into_graveyard(&[lib.library[0], stranger]), Err(ZoneError::CardNotFound));
+		assert_eq!(lib.graveyard.len(), 2);
+	}
+
+	#[test]
+	fn cards_come_back_from_the_graveyard_and_exile(){
+		let mut lib = ten_card_deck();
+		let cards: Vec<CardId> = lib.library[..3].to_vec();
+		lib.put_into_graveyard(&cards).unwrap();
+		let events = lib.return_card(cards[0], CardLocation::Graveyard, CardLocation::Hand).unwrap();
+		assert_eq!(lib.hand, vec![cards[0]]);
+		assert_eq!(events[0], CurrentEvent::CardRemovedFromGraveyard(lib.event_source(cards[0])));
+		assert_eq!(events[1], CurrentEvent::CardPutIntoHand(lib.event_source(cards[0])));
+		assert_eq!(
+			lib.return_card(cards[0], CardLocation::Graveyard, CardLocation::Battlefield),
+			Err(ZoneError::NotInZone { expected: CardLocation::Graveyard, actual: CardLocation::Hand })
+		);
+		assert_eq!(lib.return_card(cards[0], CardLocation::Hand, CardLocation::Battlefield), Err(ZoneError::NotAllowed));
+
+		let events = lib.exile_graveyard();
+		assert!(lib.graveyard.is_empty());
+		assert_eq!(lib.exile, vec![cards[1], cards[2]]);
+		assert_eq!(events.len(), 3);
+		assert!(matches!(events[0], CurrentEvent::GraveyardExiled(_)));
+		lib.return_card(cards[1], CardLocation::Exile, CardLocation::Battlefield).unwrap();
+		lib.return_card(cards[2], CardLocation::Exile, CardLocation::Library).unwrap();
+		assert_eq!(lib.battlefield, vec![cards[1]]);
+		assert_eq!(lib.library.last(), Some(&cards[2]));
+		assert!(lib.zones_are_consistent());
+	}
+
+	#[test]
+	fn graveyards_get_shuffled_into_the_library(){
+		let mut lib = ten_card_deck();
+		for _ in 0..4 {
+			lib.mill_card();
+		}
+		let events = lib.shuffle_graveyard_into_library(&mut GameRng::new(5));
+		assert!(lib.graveyard.is_empty());
+		assert_eq!(lib.library.len(), 10);
+		assert!(matches!(events[0], CurrentEvent::GraveyardShuffledIntoLibrary(_)));
+		assert!(lib.zones_are_consistent());
+	}
+
+	#[test]
+	fn decks_can_be_sent_between_threads(){
+		fn assert_send_and_sync<T: Send + Sync>() {}
+		assert_send_and_sync::<Deck>();
+	}
+}
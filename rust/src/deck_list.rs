@@ -0,0 +1,572 @@
+use std::collections::HashMap;
+use regex::Regex;
+use crate::card::{Card, CardLocation, CardType, RealCardError};
+use crate::card_db::get_card_db;
+use crate::deck::{CardListItem, Deck};
+use crate::state_manager::Player;
+
+/// The deck list formats we know how to read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeckListFormat {
+	/// what MTG Arena's export button gives you:
+	/// ```text
+	/// Commander
+	/// 1 Neyali, Suns' Vanguard (ONC) 1
+	///
+	/// Deck
+	/// 4 Lightning Bolt (M10) 146
+	/// ```
+	Arena,
+	/// MTGO's .dek files, which are xml.
+	Mtgo,
+	/// "4x Lightning Bolt" or "4 Lightning Bolt", one per line. Lines starting with "SB:", or
+	/// everything after a blank line or a "Sideboard" line, go in the sideboard.
+	PlainText,
+}
+
+impl DeckListFormat {
+	/// guesses which format some text is in.
+	pub fn detect(text: &str) -> DeckListFormat {
+		let trimmed = text.trim_start();
+		if trimmed.starts_with("<?xml") || trimmed.starts_with("<Deck") {
+			return DeckListFormat::Mtgo;
+		}
+		let arena_header = Regex::new(r"(?m)^(Deck|Commander|Companion|About)\s*$").unwrap();
+		let set_code = Regex::new(r"(?m)^\d+ .+ \([A-Za-z0-9]+\)").unwrap();
+		if arena_header.is_match(text) || set_code.is_match(text) {
+			return DeckListFormat::Arena;
+		}
+		DeckListFormat::PlainText
+	}
+}
+
+/// A deck list that got read in from (or is going to be written out to) some other program.
+#[derive(Debug, Default, PartialEq)]
+pub struct DeckList {
+	pub main: Vec<CardListItem>,
+	pub sideboard: Vec<CardListItem>,
+	pub commanders: Vec<CardListItem>,
+	pub companion: Vec<CardListItem>,
+}
+
+impl DeckList {
+	/// turns the list into a Deck. Commanders go in the command zone, and the companion goes in the
+	/// sideboard (which is where it lives when you aren't playing Commander) and becomes the deck's
+	/// companion.
+	pub fn to_deck(&self, player: Player) -> Result<Deck, RealCardError> {
+		let card_list: Vec<CardListItem> = self.main.iter().chain(self.commanders.iter()).cloned().collect();
+		let sideboard_list: Vec<CardListItem> = self.sideboard.iter().chain(self.companion.iter()).cloned().collect();
+		let mut deck = Deck::new(player, &card_list, &sideboard_list)?;
+		for commander in self.commanders.iter() {
+			let card_id = deck.search_cards(commander.0.clone()).iter()
+				.map(|card| card.id)
+				.find(|card_id| !deck.is_commander(*card_id))
+				.ok_or(RealCardError::CardNotFound(commander.0.clone()))?;
+			deck.designate_commander(card_id)
+				.map_err(|_| RealCardError::InvalidCommander(commander.0.clone()))?;
+		}
+		if let Some(companion) = self.companion.first() {
+			let card_id = *deck.sideboard.iter()
+				.find(|card_id| deck.cards[*card_id].name == companion.0)
+				.ok_or(RealCardError::CardNotFound(companion.0.clone()))?;
+			deck.companion = Some(card_id);
+		}
+		Ok(deck)
+	}
+
+	/// makes a deck list out of a Deck (tokens don't count). Cards are sorted by type, then mana
+	/// value, then name, so exporting the same deck always gives you the same thing.
+	pub fn from_deck(deck: &Deck) -> DeckList {
+		let mut main: HashMap<&str, (&Card, u32)> = HashMap::new();
+		let mut sideboard: HashMap<&str, (&Card, u32)> = HashMap::new();
+		let mut commanders: HashMap<&str, (&Card, u32)> = HashMap::new();
+		let mut companion: HashMap<&str, (&Card, u32)> = HashMap::new();
+		for card in deck.cards.values().filter(|card| !card.is_token) {
+			let section = match card.visibility_behavior.current_location {
+				_ if deck.is_commander(card.id) => &mut commanders,
+				CardLocation::Sideboard if deck.companion == Some(card.id) => &mut companion,
+				CardLocation::Sideboard => &mut sideboard,
+				_ => &mut main,
+			};
+			section.entry(card.name).or_insert((card.card, 0)).1 += 1;
+		}
+		DeckList {
+			main: sorted_card_list(main),
+			sideboard: sorted_card_list(sideboard),
+			commanders: sorted_card_list(commanders),
+			companion: sorted_card_list(companion),
+		}
+	}
+
+	/// writes the list out in Arena's import/export format.
+	pub fn to_arena(&self) -> String {
+		let mut sections = vec![];
+		if !self.commanders.is_empty() {
+			sections.push(format!("Commander\n{}", arena_lines(&self.commanders)));
+		}
+		if !self.companion.is_empty() {
+			sections.push(format!("Companion\n{}", arena_lines(&self.companion)));
+		}
+		sections.push(format!("Deck\n{}", arena_lines(&self.main)));
+		if !self.sideboard.is_empty() {
+			sections.push(format!("Sideboard\n{}", arena_lines(&self.sideboard)));
+		}
+		sections.join("\n\n")
+	}
+
+	/// writes the list out as an MTGO .dek file. MTGO keeps commanders and companions in the sideboard.
+	pub fn to_mtgo(&self) -> String {
+		let mut dek = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+			<Deck xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n\
+			\x20\x20<NetDeckID>0</NetDeckID>\n\
+			\x20\x20<PreconstructedDeckID>0</PreconstructedDeckID>\n");
+		let main = self.main.iter().map(|item| (item, false));
+		let sideboard = self.sideboard.iter()
+			.chain(self.commanders.iter())
+			.chain(self.companion.iter())
+			.map(|item| (item, true));
+		for (item, in_sideboard) in main.chain(sideboard) {
+			dek.push_str(&format!(
+				"  <Cards CatID=\"0\" Quantity=\"{}\" Sideboard=\"{}\" Name=\"{}\" Annotation=\"0\" />\n",
+				item.1, in_sideboard, escape_xml(&item.0)
+			));
+		}
+		dek.push_str("</Deck>\n");
+		dek
+	}
+
+	/// writes the list out as "4x Card Name" lines with the sideboard after a blank line. Plain text
+	/// doesn't have anywhere to put commanders, so they go at the top of the deck (and companions
+	/// at the top of the sideboard). With no main deck a blank line wouldn't mean anything, so the
+	/// sideboard gets a "Sideboard" header instead.
+	pub fn to_plain_text(&self) -> String {
+		let lines = |items: Vec<&CardListItem>| items.iter()
+			.map(|item| format!("{}x {}", item.1, item.0))
+			.collect::<Vec<String>>()
+			.join("\n");
+		let main = lines(self.commanders.iter().chain(self.main.iter()).collect());
+		let sideboard = lines(self.companion.iter().chain(self.sideboard.iter()).collect());
+		if sideboard.is_empty() {
+			return main;
+		}
+		if main.is_empty() {
+			return format!("Sideboard\n{}", sideboard);
+		}
+		format!("{}\n\n{}", main, sideboard)
+	}
+}
+
+/// writes a deck out in Arena's format.
+pub fn export_arena(deck: &Deck) -> String {
+	DeckList::from_deck(deck).to_arena()
+}
+
+/// writes a deck out as an MTGO .dek file.
+pub fn export_mtgo(deck: &Deck) -> String {
+	DeckList::from_deck(deck).to_mtgo()
+}
+
+/// writes a deck out as a plain "4x Card Name" list.
+pub fn export_plain_text(deck: &Deck) -> String {
+	DeckList::from_deck(deck).to_plain_text()
+}
+
+/// creatures first and lands last, the way pretty much every deck list does it.
+fn type_order(card: &Card) -> usize {
+	[
+		CardType::Creature, CardType::Planeswalker, CardType::Battle, CardType::Instant,
+		CardType::Sorcery, CardType::Artifact, CardType::Enchantment, CardType::Land
+	].iter()
+		.position(|card_type| card.card_types.contains(card_type))
+		.unwrap_or(8)
+}
+
+fn sorted_card_list(cards: HashMap<&str, (&Card, u32)>) -> Vec<CardListItem> {
+	let mut cards: Vec<(&str, (&Card, u32))> = cards.into_iter().collect();
+	cards.sort_by(|(a_name, (a, _)), (b_name, (b, _))| {
+		(type_order(a), a.mana_value, a_name).cmp(&(type_order(b), b.mana_value, b_name))
+	});
+	cards.into_iter().map(|(name, (_, quantity))| CardListItem(name.to_string(), quantity)).collect()
+}
+
+fn arena_lines(items: &[CardListItem]) -> String {
+	items.iter()
+		.map(|item| format!("{} {}", item.1, item.0))
+		.collect::<Vec<String>>()
+		.join("\n")
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DeckListErrorKind {
+	/// the line doesn't look like a card (or a section header) at all.
+	Malformed(String),
+	/// the card isn't in CardDB. (Names are case sensitive.)
+	UnknownCard(String),
+	/// you can't have 0 of a card.
+	InvalidQuantity(String),
+}
+
+/// Something wrong with one line of a deck list. Lines start at 1, like in a text editor.
+#[derive(Debug, PartialEq)]
+pub struct DeckListError {
+	pub line: usize,
+	pub kind: DeckListErrorKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Section {
+	Main,
+	Sideboard,
+	Commander,
+	Companion,
+	/// Arena's "About" section just has the deck's name in it.
+	Ignored,
+}
+
+struct Entry {
+	line: usize,
+	section: Section,
+	name: String,
+	quantity: u32,
+}
+
+/// reads a deck list in Arena's export format, looking up every card in CardDB.
+pub fn parse_arena(text: &str) -> Result<DeckList, Vec<DeckListError>> {
+	parse(DeckListFormat::Arena, text, &card_is_in_db)
+}
+
+/// reads an MTGO .dek file, looking up every card in CardDB.
+pub fn parse_mtgo(text: &str) -> Result<DeckList, Vec<DeckListError>> {
+	parse(DeckListFormat::Mtgo, text, &card_is_in_db)
+}
+
+/// reads a plain "4x Card Name" list, looking up every card in CardDB.
+pub fn parse_plain_text(text: &str) -> Result<DeckList, Vec<DeckListError>> {
+	parse(DeckListFormat::PlainText, text, &card_is_in_db)
+}
+
+/// reads a deck list in whatever format it's in.
+pub fn parse_deck_list(text: &str) -> Result<DeckList, Vec<DeckListError>> {
+	parse(DeckListFormat::detect(text), text, &card_is_in_db)
+}
+
+fn card_is_in_db(name: &str) -> bool {
+	get_card_db().get_card(name).is_ok()
+}
+
+/// parses a deck list and checks every card name with `card_exists`. Every problem with every line
+/// gets reported, not just the first one.
+pub(crate) fn parse(format: DeckListFormat, text: &str, card_exists: &dyn Fn(&str) -> bool)
+	-> Result<DeckList, Vec<DeckListError>> {
+	let mut errors = vec![];
+	let entries = match format {
+		DeckListFormat::Arena => read_arena(text, &mut errors),
+		DeckListFormat::Mtgo => read_mtgo(text, &mut errors),
+		DeckListFormat::PlainText => read_plain_text(text, &mut errors),
+	};
+
+	let mut deck_list = DeckList::default();
+	for entry in entries {
+		if entry.quantity == 0 {
+			errors.push(DeckListError { line: entry.line, kind: DeckListErrorKind::InvalidQuantity(entry.name) });
+			continue;
+		}
+		if !card_exists(&entry.name) {
+			errors.push(DeckListError { line: entry.line, kind: DeckListErrorKind::UnknownCard(entry.name) });
+			continue;
+		}
+		let section = match entry.section {
+			Section::Main => &mut deck_list.main,
+			Section::Sideboard => &mut deck_list.sideboard,
+			Section::Commander => &mut deck_list.commanders,
+			Section::Companion => &mut deck_list.companion,
+			Section::Ignored => continue,
+		};
+		// the same card showing up twice (like in different printings) just adds up.
+		match section.iter_mut().find(|item| item.0 == entry.name) {
+			Some(item) => item.1 += entry.quantity,
+			None => section.push(CardListItem(entry.name, entry.quantity)),
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(deck_list)
+	} else {
+		errors.sort_by_key(|error| error.line);
+		Err(errors)
+	}
+}
+
+fn read_arena(text: &str, errors: &mut Vec<DeckListError>) -> Vec<Entry> {
+	let card_line = Regex::new(r"^(\d+)x? (.+?)(?: \([A-Za-z0-9]+\)(?: \S+)?)?$").unwrap();
+	let mut section = Section::Main;
+	let mut entries = vec![];
+
+	for (i, line) in text.lines().enumerate() {
+		let line = line.trim();
+		match line {
+			"" => continue,
+			"Deck" => { section = Section::Main; continue; },
+			"Sideboard" => { section = Section::Sideboard; continue; },
+			"Commander" => { section = Section::Commander; continue; },
+			"Companion" => { section = Section::Companion; continue; },
+			"About" => { section = Section::Ignored; continue; },
+			_ => {}
+		}
+		if section == Section::Ignored {
+			continue;
+		}
+		match card_line.captures(line) {
+			Some(captures) => entries.push(Entry {
+				line: i + 1,
+				section,
+				name: captures[2].to_string(),
+				quantity: captures[1].parse().unwrap_or(0),
+			}),
+			None => errors.push(DeckListError { line: i + 1, kind: DeckListErrorKind::Malformed(line.to_string()) }),
+		}
+	}
+	entries
+}
+
+fn read_plain_text(text: &str, errors: &mut Vec<DeckListError>) -> Vec<Entry> {
+	let card_line = Regex::new(r"^(SB:\s*)?(\d+)x? (.+)$").unwrap();
+	let mut section = Section::Main;
+	let mut entries = vec![];
+
+	for (i, line) in text.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() {
+			// a blank line after the main deck means the sideboard's next.
+			if !entries.is_empty() {
+				section = Section::Sideboard;
+			}
+			continue;
+		}
+		if line.trim_end_matches(':').eq_ignore_ascii_case("sideboard") {
+			section = Section::Sideboard;
+			continue;
+		}
+		match card_line.captures(line) {
+			Some(captures) => entries.push(Entry {
+				line: i + 1,
+				section: if captures.get(1).is_some() { Section::Sideboard } else { section },
+				name: captures[3].trim().to_string(),
+				quantity: captures[2].parse().unwrap_or(0),
+			}),
+			None => errors.push(DeckListError { line: i + 1, kind: DeckListErrorKind::Malformed(line.to_string()) }),
+		}
+	}
+	entries
+}
+
+fn read_mtgo(text: &str, errors: &mut Vec<DeckListError>) -> Vec<Entry> {
+	let attribute = Regex::new(r#"(\w+)="([^"]*)""#).unwrap();
+	let mut entries = vec![];
+
+	for (i, line) in text.lines().enumerate() {
+		let line = line.trim();
+		if !line.starts_with("<Cards") {
+			continue;
+		}
+		let mut name = None;
+		let mut quantity = None;
+		let mut section = Section::Main;
+		for captures in attribute.captures_iter(line) {
+			match &captures[1] {
+				"Name" => name = Some(unescape_xml(&captures[2])),
+				"Quantity" => quantity = captures[2].parse().ok(),
+				"Sideboard" if &captures[2] == "true" => section = Section::Sideboard,
+				_ => {}
+			}
+		}
+		match (name, quantity) {
+			(Some(name), Some(quantity)) => entries.push(Entry { line: i + 1, section, name, quantity }),
+			_ => errors.push(DeckListError { line: i + 1, kind: DeckListErrorKind::Malformed(line.to_string()) }),
+		}
+	}
+	entries
+}
+
+fn escape_xml(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('\'', "&apos;")
+		.replace('"', "&quot;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+}
+
+fn unescape_xml(text: &str) -> String {
+	text.replace("&apos;", "'")
+		.replace("&quot;", "\"")
+		.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn known_cards(name: &str) -> bool {
+		[
+			"Lightning Bolt", "Mountain", "Soldier of Fortune", "Neyali, Suns' Vanguard",
+			"Dragon's Rage Channeler", "Relentless Rats"
+		].contains(&name)
+	}
+
+	#[test]
+	fn reads_arena_exports(){
+		let text = "About\nName Burn\n\nCommander\n1 Neyali, Suns' Vanguard (ONC) 1\n\nDeck\n4 Lightning Bolt (M10) 146\n20 Mountain (ZNR) 276\n\nSideboard\n2 Soldier of Fortune (JMP) 148";
+		assert_eq!(DeckListFormat::detect(text), DeckListFormat::Arena);
+		let deck_list = parse(DeckListFormat::Arena, text, &known_cards).unwrap();
+		assert_eq!(deck_list.main, vec![
+			CardListItem("Lightning Bolt".to_string(), 4),
+			CardListItem("Mountain".to_string(), 20),
+		]);
+		assert_eq!(deck_list.sideboard, vec![CardListItem("Soldier of Fortune".to_string(), 2)]);
+		assert_eq!(deck_list.commanders, vec![CardListItem("Neyali, Suns' Vanguard".to_string(), 1)]);
+	}
+
+	#[test]
+	fn reports_every_bad_line(){
+		let text = "Deck\n4 Lightning Bolt (M10) 146\nfour Mountain\n2 Lightning Blot (M10) 146\n0 Mountain";
+		let errors = parse(DeckListFormat::Arena, text, &known_cards).unwrap_err();
+		assert_eq!(errors, vec![
+			DeckListError { line: 3, kind: DeckListErrorKind::Malformed("four Mountain".to_string()) },
+			DeckListError { line: 4, kind: DeckListErrorKind::UnknownCard("Lightning Blot".to_string()) },
+			DeckListError { line: 5, kind: DeckListErrorKind::InvalidQuantity("Mountain".to_string()) },
+		]);
+	}
+
+	#[test]
+	fn reads_mtgo_dek_files(){
+		let text = r#"<?xml version="1.0" encoding="utf-8"?>
+<Deck xmlns:xsd="http://www.w3.org/2001/XMLSchema" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <NetDeckID>0</NetDeckID>
+  <PreconstructedDeckID>0</PreconstructedDeckID>
+  <Cards CatID="71364" Quantity="4" Sideboard="false" Name="Dragon&apos;s Rage Channeler" Annotation="0" />
+  <Cards CatID="16628" Quantity="18" Sideboard="false" Name="Mountain" Annotation="0" />
+  <Cards CatID="12345" Quantity="3" Sideboard="true" Name="Soldier of Fortune" Annotation="0" />
+  <Cards CatID="1" Sideboard="true" Name="Mountain" />
+</Deck>"#;
+		assert_eq!(DeckListFormat::detect(text), DeckListFormat::Mtgo);
+		let errors = parse(DeckListFormat::Mtgo, text, &known_cards).unwrap_err();
+		assert_eq!(errors.len(), 1);
+		assert_eq!(errors[0].line, 8);
+
+		let text = text.replace("  <Cards CatID=\"1\" Sideboard=\"true\" Name=\"Mountain\" />\n", "");
+		let deck_list = parse(DeckListFormat::Mtgo, &text, &known_cards).unwrap();
+		assert_eq!(deck_list.main, vec![
+			CardListItem("Dragon's Rage Channeler".to_string(), 4),
+			CardListItem("Mountain".to_string(), 18),
+		]);
+		assert_eq!(deck_list.sideboard, vec![CardListItem("Soldier of Fortune".to_string(), 3)]);
+	}
+
+	#[test]
+	fn reads_plain_text_lists(){
+		let text = "4x Lightning Bolt\n2 Lightning Bolt\n60 Relentless Rats\n\n3x Soldier of Fortune\nSB: 1 Mountain";
+		assert_eq!(DeckListFormat::detect(text), DeckListFormat::PlainText);
+		let deck_list = parse(DeckListFormat::PlainText, text, &known_cards).unwrap();
+		assert_eq!(deck_list.main, vec![
+			CardListItem("Lightning Bolt".to_string(), 6),
+			CardListItem("Relentless Rats".to_string(), 60),
+		]);
+		assert_eq!(deck_list.sideboard, vec![
+			CardListItem("Soldier of Fortune".to_string(), 3),
+			CardListItem("Mountain".to_string(), 1),
+		]);
+	}
+
+	fn example_list() -> DeckList {
+		DeckList {
+			main: vec![
+				CardListItem("Dragon's Rage Channeler".to_string(), 4),
+				CardListItem("Lightning Bolt".to_string(), 4),
+				CardListItem("Mountain".to_string(), 16),
+			],
+			sideboard: vec![CardListItem("Soldier of Fortune".to_string(), 2)],
+			commanders: vec![],
+			companion: vec![],
+		}
+	}
+
+	#[test]
+	fn arena_exports_round_trip(){
+		let mut deck_list = example_list();
+		deck_list.commanders.push(CardListItem("Neyali, Suns' Vanguard".to_string(), 1));
+		let exported = deck_list.to_arena();
+		assert!(exported.starts_with("Commander\n1 Neyali, Suns' Vanguard\n\nDeck\n4 Dragon's Rage Channeler\n"));
+		assert_eq!(parse(DeckListFormat::detect(&exported), &exported, &known_cards).unwrap(), deck_list);
+	}
+
+	#[test]
+	fn mtgo_exports_round_trip(){
+		let deck_list = example_list();
+		let exported = deck_list.to_mtgo();
+		assert!(exported.contains("Name=\"Dragon&apos;s Rage Channeler\""));
+		assert_eq!(parse(DeckListFormat::detect(&exported), &exported, &known_cards).unwrap(), deck_list);
+	}
+
+	#[test]
+	fn plain_text_exports_round_trip(){
+		let deck_list = example_list();
+		let exported = deck_list.to_plain_text();
+		assert_eq!(exported, "4x Dragon's Rage Channeler\n4x Lightning Bolt\n16x Mountain\n\n2x Soldier of Fortune");
+		assert_eq!(parse(DeckListFormat::detect(&exported), &exported, &known_cards).unwrap(), deck_list);
+
+		let sideboard_only = DeckList { sideboard: deck_list.sideboard.clone(), ..Default::default() };
+		let exported = sideboard_only.to_plain_text();
+		assert_eq!(parse(DeckListFormat::detect(&exported), &exported, &known_cards).unwrap(), sideboard_only);
+	}
+
+	#[test]
+	fn exporting_a_deck_sorts_by_type_then_mana_value_then_name(){
+		let deck_list = parse_plain_text("16 Mountain\n4 Lightning Bolt\n2 Grizzly Bears\n4 Dragon's Rage Channeler\n\n2 Soldier of Fortune").unwrap();
+		let deck = deck_list.to_deck(Player::new("Me")).unwrap();
+		let exported = export_arena(&deck);
+		assert_eq!(exported, "Deck\n4 Dragon's Rage Channeler\n2 Grizzly Bears\n4 Lightning Bolt\n16 Mountain\n\nSideboard\n2 Soldier of Fortune");
+		let reimported = parse_arena(&exported).unwrap();
+		assert_eq!(reimported, DeckList::from_deck(&deck));
+	}
+
+	#[test]
+	fn commanders_go_to_the_command_zone_and_come_back_out(){
+		let deck_list = parse_arena("Commander\n1 Neyali, Suns' Vanguard\n\nDeck\n1 Boros Signet\n98 Plains").unwrap();
+		let deck = deck_list.to_deck(Player::new("Blake")).unwrap();
+		assert_eq!(deck.command.len(), 1);
+		assert_eq!(deck.library.len(), 99);
+		assert_eq!(DeckList::from_deck(&deck), deck_list);
+
+		let not_a_commander = parse_arena("Commander\n1 Boros Signet\n\nDeck\n99 Plains").unwrap();
+		assert_eq!(
+			not_a_commander.to_deck(Player::new("Blake")).unwrap_err(),
+			RealCardError::InvalidCommander("Boros Signet".to_string())
+		);
+	}
+
+	#[test]
+	fn companions_survive_exporting_and_importing_again(){
+		let deck_list = parse_arena("Companion\n1 Lurrus of the Dream-Den\n\nDeck\n4 Lightning Bolt\n56 Mountain\n\nSideboard\n2 Soldier of Fortune").unwrap();
+		let deck = deck_list.to_deck(Player::new("Me")).unwrap();
+		let companion = deck.companion.unwrap();
+		assert_eq!(deck.cards[&companion].name, "Lurrus of the Dream-Den");
+		assert!(deck.sideboard.contains(&companion));
+
+		let exported = export_arena(&deck);
+		assert!(exported.starts_with("Companion\n1 Lurrus of the Dream-Den\n\nDeck\n"));
+		let reimported = parse_arena(&exported).unwrap();
+		assert_eq!(reimported, deck_list);
+		let deck = reimported.to_deck(Player::new("Me")).unwrap();
+		assert_eq!(DeckList::from_deck(&deck), deck_list);
+	}
+
+	#[test]
+	fn parse_arena_looks_cards_up_in_card_db(){
+		let deck_list = parse_arena("Deck\n4 Lightning Bolt (M10) 146\n20 Mountain").unwrap();
+		let deck = deck_list.to_deck(Player::new("Me")).unwrap();
+		assert_eq!(deck.library.len(), 24);
+		assert!(parse_arena("Deck\n4 Lightning Blot").is_err());
+	}
+}
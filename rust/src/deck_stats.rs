@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use regex::Regex;
+use serde::Serialize;
+use crate::card::{Card, CardLocation, CardType, RealCardError};
+use crate::card_db::get_card_db;
+use crate::colors::Color;
+use crate::deck::{CardListItem, Deck};
+
+/// A number for each color of mana (plus colorless).
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ColorCounts {
+	pub w: u32,
+	pub u: u32,
+	pub b: u32,
+	pub r: u32,
+	pub g: u32,
+	pub c: u32,
+}
+
+impl ColorCounts {
+	/// adds to the count for a color. Hybrid symbols count for every color in them; anything
+	/// that isn't a color of mana (X, snow, etc.) is ignored.
+	pub fn add(&mut self, color: &Color, amount: u32) {
+		match color {
+			Color::W => self.w += amount,
+			Color::U => self.u += amount,
+			Color::B => self.b += amount,
+			Color::R => self.r += amount,
+			Color::G => self.g += amount,
+			Color::C => self.c += amount,
+			Color::MultiColor { colors, .. } => {
+				for color in colors.iter() {
+					// the generic half of {2/U} doesn't count as a pip.
+					if *color != Color::C {
+						self.add(color, amount);
+					}
+				}
+			},
+			_ => {}
+		}
+	}
+}
+
+/// How many cards there are of each card type. A card with more than one type (like an artifact
+/// creature) counts for each of them.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TypeCounts {
+	pub creatures: u32,
+	pub instants: u32,
+	pub sorceries: u32,
+	pub artifacts: u32,
+	pub enchantments: u32,
+	pub planeswalkers: u32,
+	pub battles: u32,
+	pub lands: u32,
+}
+
+impl TypeCounts {
+	fn add(&mut self, card_types: &[CardType], amount: u32) {
+		for card_type in card_types.iter() {
+			match card_type {
+				CardType::Creature => self.creatures += amount,
+				CardType::Instant => self.instants += amount,
+				CardType::Sorcery => self.sorceries += amount,
+				CardType::Artifact => self.artifacts += amount,
+				CardType::Enchantment => self.enchantments += amount,
+				CardType::Planeswalker => self.planeswalkers += amount,
+				CardType::Battle => self.battles += amount,
+				CardType::Land => self.lands += amount,
+				_ => {}
+			}
+		}
+	}
+}
+
+/// Deckbuilding numbers for a deck. Serializes to json for the front end with to_json.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct DeckStats {
+	pub card_count: u32,
+	pub land_count: u32,
+	/// mana value -> how many nonland cards of each type have that mana value.
+	pub curve: BTreeMap<u8, TypeCounts>,
+	pub types: TypeCounts,
+	/// colored mana symbols in mana costs, like the two {W}s in {3}{W}{W}.
+	pub pips: ColorCounts,
+	/// how many lands and mana rocks can make each color of mana. Ones that make "any color"
+	/// only count for the colors in the deck's color identity.
+	pub mana_sources: ColorCounts,
+	pub average_mana_value: f64,
+	pub average_mana_value_without_lands: f64,
+}
+
+impl DeckStats {
+	/// stats for the cards in a deck that aren't in the sideboard (tokens don't count).
+	pub fn from_deck(deck: &Deck) -> DeckStats {
+		let cards = deck.cards.values()
+			.filter(|card| !card.is_token)
+			.filter(|card| card.visibility_behavior.current_location != CardLocation::Sideboard)
+			.map(|card| (card.card, 1));
+		DeckStats::from_cards(cards)
+	}
+
+	/// stats for a card list; every card gets looked up in CardDB.
+	pub fn from_card_list(card_list: &[CardListItem]) -> Result<DeckStats, RealCardError> {
+		let db = get_card_db();
+		let mut cards = vec![];
+		for item in card_list.iter() {
+			let card = db.get_card(&item.0).map_err(|_| RealCardError::CardNotFound(item.0.clone()))?;
+			cards.push((card, item.1));
+		}
+		Ok(DeckStats::from_cards(cards.into_iter()))
+	}
+
+	fn from_cards<'a>(cards: impl Iterator<Item = (&'a Card, u32)>) -> DeckStats {
+		let cards: Vec<(&Card, u32)> = cards.collect();
+		let mut deck_colors = vec![];
+		for color in cards.iter().flat_map(|(card, _)| card.color_identity.iter()) {
+			if !deck_colors.contains(color) {
+				deck_colors.push(color.clone());
+			}
+		}
+		let mut stats = DeckStats::default();
+		let mut total_mana_value = 0;
+		for (card, quantity) in cards {
+			let is_land = card.card_types.contains(&CardType::Land);
+			stats.card_count += quantity;
+			stats.types.add(&card.card_types, quantity);
+			total_mana_value += card.mana_value as u32 * quantity;
+			for (color, amount) in card.mana_cost.cost.iter() {
+				if *color != Color::C {
+					stats.pips.add(color, *amount as u32 * quantity);
+				}
+			}
+			if is_land {
+				stats.land_count += quantity;
+			} else {
+				stats.curve.entry(card.mana_value).or_default().add(&card.card_types, quantity);
+			}
+			if is_land || card.card_types.contains(&CardType::Artifact) {
+				for color in mana_produced_with_any_color_as(card, &deck_colors).iter() {
+					stats.mana_sources.add(color, quantity);
+				}
+			}
+		}
+		let nonlands = stats.card_count - stats.land_count;
+		if stats.card_count > 0 {
+			stats.average_mana_value = total_mana_value as f64 / stats.card_count as f64;
+		}
+		if nonlands > 0 {
+			stats.average_mana_value_without_lands = total_mana_value as f64 / nonlands as f64;
+		}
+		stats
+	}
+
+	pub fn to_json(&self) -> String {
+		serde_json::to_string(self).unwrap()
+	}
+}
+
+/// every color of mana a card's mana abilities can make, from the "Add {R}{W}" bits of its text.
+/// "one mana of any color" counts as all five.
+pub fn mana_produced(card: &Card) -> Vec<Color> {
+	mana_produced_with_any_color_as(card, &[Color::W, Color::U, Color::B, Color::R, Color::G])
+}
+
+/// like mana_produced, but "one mana of any color" counts as `any_color`, like the colors a deck
+/// can actually use.
+fn mana_produced_with_any_color_as(card: &Card, any_color: &[Color]) -> Vec<Color> {
+	static ADD: OnceLock<Regex> = OnceLock::new();
+	static SYMBOL: OnceLock<Regex> = OnceLock::new();
+	let add = ADD.get_or_init(|| Regex::new(r"Add ([^.]+)").unwrap());
+	let symbol = SYMBOL.get_or_init(|| Regex::new(r"\{([WUBRGC])}").unwrap());
+	let mut colors = vec![];
+	for captures in add.captures_iter(&card.description) {
+		let produced = &captures[1];
+		if produced.contains("any color") {
+			colors.extend(any_color.iter().cloned());
+		}
+		for symbol in symbol.captures_iter(produced) {
+			colors.push(symbol[1].parse().unwrap());
+		}
+	}
+	let mut unique = vec![];
+	for color in colors {
+		if !unique.contains(&color) {
+			unique.push(color);
+		}
+	}
+	unique
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn list(cards: &[(&str, u32)]) -> Vec<CardListItem> {
+		cards.iter().map(|(name, quantity)| CardListItem(name.to_string(), *quantity)).collect()
+	}
+
+	#[test]
+	fn counts_curve_types_and_lands(){
+		let stats = DeckStats::from_card_list(&list(&[
+			("Dragon's Rage Channeler", 4), ("Lightning Bolt", 4), ("Siege-Gang Commander", 2),
+			("Boros Signet", 2), ("Mountain", 8),
+		])).unwrap();
+		assert_eq!(stats.card_count, 20);
+		assert_eq!(stats.land_count, 8);
+		assert_eq!(stats.types.creatures, 6);
+		assert_eq!(stats.types.instants, 4);
+		assert_eq!(stats.curve[&1].creatures, 4);
+		assert_eq!(stats.curve[&1].instants, 4);
+		assert_eq!(stats.curve[&5].creatures, 2);
+		assert!(!stats.curve.contains_key(&0));
+		assert_eq!(stats.average_mana_value, 22.0 / 20.0);
+		assert_eq!(stats.average_mana_value_without_lands, 22.0 / 12.0);
+	}
+
+	#[test]
+	fn counts_pips_and_mana_sources(){
+		let stats = DeckStats::from_card_list(&list(&[
+			("Neyali, Suns' Vanguard", 1), ("Elspeth Tirel", 1), ("Advice from the Fae", 1),
+			("Boros Signet", 1), ("Sol Ring", 1), ("Command Tower", 1), ("Plains", 10),
+		])).unwrap();
+		assert_eq!(stats.pips, ColorCounts { w: 3, u: 3, b: 0, r: 1, g: 0, c: 0 });
+		// Command Tower only makes the deck's colors: white, blue and red.
+		assert_eq!(stats.mana_sources, ColorCounts { w: 12, u: 1, b: 0, r: 2, g: 0, c: 1 });
+	}
+
+	#[test]
+	fn stats_serialize_to_json(){
+		let stats = DeckStats::from_card_list(&list(&[("Grizzly Bears", 2), ("Forest", 2)])).unwrap();
+		let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+		assert_eq!(json["curve"]["2"]["creatures"], 2);
+		assert_eq!(json["mana_sources"]["g"], 2);
+		assert_eq!(json["land_count"], 2);
+	}
+}
@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use regex::Regex;
+use crate::card::{Card, card_is_basic_land};
+use crate::card_db::get_card_db;
+use crate::colors::Color;
+use crate::commander::{can_be_commander, can_be_commanders_together, is_background};
+use crate::deck::{CardListItem, Deck};
+use crate::deck_list::DeckList;
+
+/// The formats a deck can be checked against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+	Standard,
+	Pioneer,
+	Modern,
+	/// 100 cards (commander included), one of each card, and everything has to be in the
+	/// commander's color identity.
+	Commander,
+	/// draft and sealed: 40 cards, and you can have as many of anything as you opened.
+	Limited,
+}
+
+impl Format {
+	/// the smallest the main deck (plus commanders) can be.
+	pub fn min_deck_size(&self) -> u32 {
+		match self {
+			Format::Commander => 100,
+			Format::Limited => 40,
+			_ => 60,
+		}
+	}
+
+	/// the biggest the main deck (plus commanders) can be, if there's a limit.
+	pub fn max_deck_size(&self) -> Option<u32> {
+		match self {
+			Format::Commander => Some(100),
+			_ => None,
+		}
+	}
+
+	/// the biggest the sideboard (companion included) can be, if there's a limit. In limited the
+	/// whole card pool is your sideboard.
+	pub fn max_sideboard_size(&self) -> Option<u32> {
+		match self {
+			Format::Standard | Format::Pioneer | Format::Modern => Some(15),
+			Format::Commander | Format::Limited => None,
+		}
+	}
+
+	/// how many copies of a card (that isn't a basic land) you can have, if there's a limit.
+	pub fn max_copies(&self) -> Option<u32> {
+		match self {
+			Format::Commander => Some(1),
+			Format::Limited => None,
+			_ => Some(4),
+		}
+	}
+
+	/// what mtgjson calls the format in a card's legalities.
+	fn legality_key(&self) -> Option<&'static str> {
+		match self {
+			Format::Standard => Some("standard"),
+			Format::Pioneer => Some("pioneer"),
+			Format::Modern => Some("modern"),
+			Format::Commander => Some("commander"),
+			Format::Limited => None,
+		}
+	}
+}
+
+/// One thing wrong with a deck.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeckViolation {
+	TooFewCards { count: u32, minimum: u32 },
+	TooManyCards { count: u32, maximum: u32 },
+	SideboardTooBig { count: u32, maximum: u32 },
+	TooManyCopies { name: String, count: u32, maximum: u32 },
+	Banned(String),
+	/// the card isn't legal in the format at all (mtgjson doesn't list it for the format).
+	NotLegal(String),
+	/// the card isn't in CardDB, so there's no way to know if it's allowed.
+	UnknownCard(String),
+	NoCommander,
+	/// only partners can share the command zone.
+	TooManyCommanders(u32),
+	/// commanders have to be legendary creatures (or say they can be your commander).
+	InvalidCommander(String),
+	OutsideColorIdentity(String),
+}
+
+/// Checks decks against a format's deck building rules. Cards banned in the format (according to
+/// mtgjson) are always caught; `banned` is for anything else you want to ban, like a playgroup's
+/// house bans.
+#[derive(Debug)]
+pub struct DeckValidator {
+	pub format: Format,
+	pub banned: Vec<String>,
+}
+
+impl DeckValidator {
+	pub fn new(format: Format) -> DeckValidator {
+		DeckValidator { format, banned: vec![] }
+	}
+
+	/// returns every rule the deck list breaks, not just the first one.
+	pub fn validate(&self, deck_list: &DeckList) -> Result<(), Vec<DeckViolation>> {
+		let mut violations = vec![];
+		let db = get_card_db();
+
+		let deck_size = total(&deck_list.main) + total(&deck_list.commanders);
+		if deck_size < self.format.min_deck_size() {
+			violations.push(DeckViolation::TooFewCards { count: deck_size, minimum: self.format.min_deck_size() });
+		}
+		if let Some(maximum) = self.format.max_deck_size() {
+			if deck_size > maximum {
+				violations.push(DeckViolation::TooManyCards { count: deck_size, maximum });
+			}
+		}
+		let sideboard_size = total(&deck_list.sideboard) + total(&deck_list.companion);
+		if let Some(maximum) = self.format.max_sideboard_size() {
+			if sideboard_size > maximum {
+				violations.push(DeckViolation::SideboardTooBig { count: sideboard_size, maximum });
+			}
+		}
+
+		// copies are counted across the deck and sideboard together, in the order they show up.
+		let mut copies: Vec<(&str, u32)> = vec![];
+		for item in deck_list.main.iter()
+			.chain(deck_list.commanders.iter())
+			.chain(deck_list.sideboard.iter())
+			.chain(deck_list.companion.iter()) {
+			match copies.iter_mut().find(|(name, _)| *name == item.0) {
+				Some((_, count)) => *count += item.1,
+				None => copies.push((&item.0, item.1)),
+			}
+		}
+
+		let mut cards: HashMap<&str, &Card> = HashMap::new();
+		for (name, count) in copies.iter() {
+			let card = match db.get_card(name) {
+				Ok(card) => card,
+				Err(_) => {
+					violations.push(DeckViolation::UnknownCard(name.to_string()));
+					continue;
+				}
+			};
+			cards.insert(name, card);
+			if self.is_banned(card) {
+				violations.push(DeckViolation::Banned(name.to_string()));
+			} else if !self.is_legal(card) {
+				violations.push(DeckViolation::NotLegal(name.to_string()));
+			}
+			if let Some(maximum) = self.max_copies_of(card) {
+				if *count > maximum {
+					violations.push(DeckViolation::TooManyCopies { name: name.to_string(), count: *count, maximum });
+				}
+			}
+		}
+
+		if self.format == Format::Commander {
+			violations.extend(check_commanders(deck_list, &cards));
+		}
+
+		if violations.is_empty() {
+			Ok(())
+		} else {
+			Err(violations)
+		}
+	}
+
+	/// validates a Deck by turning it back into a deck list first.
+	pub fn validate_deck(&self, deck: &Deck) -> Result<(), Vec<DeckViolation>> {
+		self.validate(&DeckList::from_deck(deck))
+	}
+
+	fn is_banned(&self, card: &Card) -> bool {
+		let banned_in_format = self.format.legality_key()
+			.and_then(|format| card.legalities.get(format))
+			.is_some_and(|legality| legality == "Banned");
+		banned_in_format || self.banned.contains(&card.name)
+	}
+
+	/// cards that were never printed in a format's sets don't get a legality for it at all.
+	fn is_legal(&self, card: &Card) -> bool {
+		match self.format.legality_key() {
+			Some(format) => card.legalities.contains_key(format),
+			None => true,
+		}
+	}
+
+	fn max_copies_of(&self, card: &Card) -> Option<u32> {
+		if card_is_basic_land(&card.card_types, &card.supertypes) {
+			return None;
+		}
+		match copies_allowed_by_card_text(card) {
+			Some(allowed) => allowed,
+			None => self.format.max_copies(),
+		}
+	}
+}
+
+/// some cards, like Relentless Rats, say how many of them a deck can have, which beats the
+/// format's limit. Some(None) means any number.
+fn copies_allowed_by_card_text(card: &Card) -> Option<Option<u32>> {
+	let re = Regex::new(r"A deck can have (any number of|up to (\w+)) cards named").unwrap();
+	let captures = re.captures(&card.description)?;
+	let up_to = match captures.get(2) {
+		Some(up_to) => up_to.as_str(),
+		None => return Some(None),
+	};
+	let words = ["zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten"];
+	match words.iter().position(|word| *word == up_to) {
+		Some(count) => Some(Some(count as u32)),
+		None => Some(up_to.parse().ok()),
+	}
+}
+
+fn check_commanders(deck_list: &DeckList, cards: &HashMap<&str, &Card>) -> Vec<DeckViolation> {
+	let mut violations = vec![];
+	let commanders: Vec<&Card> = deck_list.commanders.iter()
+		.filter_map(|item| cards.get(item.0.as_str()).copied())
+		.collect();
+	let commander_count = total(&deck_list.commanders);
+	if commander_count == 0 {
+		violations.push(DeckViolation::NoCommander);
+		return violations;
+	}
+	let paired = commanders.len() == 2 && can_be_commanders_together(commanders[0], commanders[1]);
+	if commander_count > 2 || (commander_count == 2 && !paired) {
+		violations.push(DeckViolation::TooManyCommanders(commander_count));
+	}
+	for commander in commanders.iter() {
+		let allowed = can_be_commander(commander) || (paired && is_background(commander));
+		if !allowed {
+			violations.push(DeckViolation::InvalidCommander(commander.name.clone()));
+		}
+	}
+
+	let color_identity: Vec<&Color> = commanders.iter().flat_map(|card| card.color_identity.iter()).collect();
+	for item in deck_list.main.iter().chain(deck_list.companion.iter()) {
+		if let Some(card) = cards.get(item.0.as_str()) {
+			if card.color_identity.iter().any(|color| !color_identity.contains(&color)) {
+				violations.push(DeckViolation::OutsideColorIdentity(item.0.clone()));
+			}
+		}
+	}
+	violations
+}
+
+fn total(items: &[CardListItem]) -> u32 {
+	items.iter().map(|item| item.1).sum()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn list(cards: &[(&str, u32)]) -> Vec<CardListItem> {
+		cards.iter().map(|(name, quantity)| CardListItem(name.to_string(), *quantity)).collect()
+	}
+
+	#[test]
+	fn constructed_decks_need_sixty_cards_and_at_most_four_copies(){
+		let deck_list = DeckList {
+			main: list(&[("Lightning Bolt", 5), ("Mountain", 40)]),
+			sideboard: list(&[("Soldier of Fortune", 16)]),
+			..Default::default()
+		};
+		let violations = DeckValidator::new(Format::Modern).validate(&deck_list).unwrap_err();
+		assert_eq!(violations, vec![
+			DeckViolation::TooFewCards { count: 45, minimum: 60 },
+			DeckViolation::SideboardTooBig { count: 16, maximum: 15 },
+			DeckViolation::TooManyCopies { name: "Lightning Bolt".to_string(), count: 5, maximum: 4 },
+			DeckViolation::TooManyCopies { name: "Soldier of Fortune".to_string(), count: 16, maximum: 4 },
+		]);
+	}
+
+	#[test]
+	fn copies_in_the_sideboard_count_too(){
+		let deck_list = DeckList {
+			main: list(&[("Lightning Bolt", 4), ("Mountain", 56)]),
+			sideboard: list(&[("Lightning Bolt", 1)]),
+			..Default::default()
+		};
+		let violations = DeckValidator::new(Format::Modern).validate(&deck_list).unwrap_err();
+		assert_eq!(violations, vec![
+			DeckViolation::TooManyCopies { name: "Lightning Bolt".to_string(), count: 5, maximum: 4 },
+		]);
+	}
+
+	#[test]
+	fn cards_the_format_doesnt_list_arent_legal(){
+		let deck_list = DeckList {
+			main: list(&[("Lightning Bolt", 4), ("Mountain", 56)]),
+			..Default::default()
+		};
+		let violations = DeckValidator::new(Format::Standard).validate(&deck_list).unwrap_err();
+		assert_eq!(violations, vec![DeckViolation::NotLegal("Lightning Bolt".to_string())]);
+		assert_eq!(DeckValidator::new(Format::Modern).validate(&deck_list), Ok(()));
+		// limited doesn't care what's been printed where.
+		let deck_list = DeckList { main: list(&[("Lightning Bolt", 4), ("Mountain", 36)]), ..Default::default() };
+		assert_eq!(DeckValidator::new(Format::Limited).validate(&deck_list), Ok(()));
+	}
+
+	#[test]
+	fn any_number_of_relentless_rats_is_fine(){
+		let deck_list = DeckList {
+			main: list(&[("Relentless Rats", 30), ("Swamp", 30)]),
+			..Default::default()
+		};
+		assert_eq!(DeckValidator::new(Format::Modern).validate(&deck_list), Ok(()));
+	}
+
+	#[test]
+	fn limited_decks_need_forty_cards_and_no_copy_limit(){
+		let deck_list = DeckList {
+			main: list(&[("Grizzly Bears", 7), ("Forest", 17)]),
+			..Default::default()
+		};
+		let violations = DeckValidator::new(Format::Limited).validate(&deck_list).unwrap_err();
+		assert_eq!(violations, vec![DeckViolation::TooFewCards { count: 24, minimum: 40 }]);
+	}
+
+	#[test]
+	fn banned_cards_are_caught(){
+		let deck_list = DeckList {
+			main: list(&[("Arcum's Astrolabe", 4), ("Opt", 4), ("Island", 52)]),
+			..Default::default()
+		};
+		let mut validator = DeckValidator::new(Format::Modern);
+		validator.banned.push("Opt".to_string());
+		let violations = validator.validate(&deck_list).unwrap_err();
+		assert_eq!(violations, vec![
+			DeckViolation::Banned("Arcum's Astrolabe".to_string()),
+			DeckViolation::Banned("Opt".to_string()),
+		]);
+		assert!(!DeckValidator::new(Format::Commander).is_banned(get_card_db().get_card("Arcum's Astrolabe").unwrap()));
+	}
+
+	#[test]
+	fn commander_decks_are_singleton_and_in_color_identity(){
+		let deck_list = DeckList {
+			commanders: list(&[("Neyali, Suns' Vanguard", 1)]),
+			main: list(&[
+				("Boros Signet", 1), ("Sol Ring", 2), ("Murder", 1), ("Lightning Bolt", 1),
+				("Plains", 47), ("Mountain", 47),
+			]),
+			..Default::default()
+		};
+		let violations = DeckValidator::new(Format::Commander).validate(&deck_list).unwrap_err();
+		assert_eq!(violations, vec![
+			DeckViolation::TooManyCopies { name: "Sol Ring".to_string(), count: 2, maximum: 1 },
+			DeckViolation::OutsideColorIdentity("Murder".to_string()),
+		]);
+	}
+
+	#[test]
+	fn commander_decks_need_a_legendary_commander(){
+		let deck_list = DeckList {
+			commanders: list(&[("Grizzly Bears", 1)]),
+			main: list(&[("Forest", 99)]),
+			..Default::default()
+		};
+		let violations = DeckValidator::new(Format::Commander).validate(&deck_list).unwrap_err();
+		assert_eq!(violations, vec![DeckViolation::InvalidCommander("Grizzly Bears".to_string())]);
+
+		let deck_list = DeckList { main: list(&[("Forest", 101)]), ..Default::default() };
+		let violations = DeckValidator::new(Format::Commander).validate(&deck_list).unwrap_err();
+		assert_eq!(violations, vec![
+			DeckViolation::TooManyCards { count: 101, maximum: 100 },
+			DeckViolation::NoCommander,
+		]);
+	}
+}
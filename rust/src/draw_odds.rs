@@ -0,0 +1,283 @@
+use serde::Serialize;
+use crate::card::{Card, CardLocation, CardType};
+use crate::colors::Color;
+use crate::deck::{Deck, OPENING_HAND_SIZE};
+use crate::deck_stats::mana_produced;
+
+/// something a card has to be to count, like "is a Plains" or "costs 2".
+pub type CardPredicate = Box<dyn Fn(&Card) -> bool>;
+
+pub fn named(name: &str) -> CardPredicate {
+	let name = name.to_string();
+	Box::new(move |card| card.name == name)
+}
+
+pub fn has_type(card_type: CardType) -> CardPredicate {
+	Box::new(move |card| card.card_types.contains(&card_type))
+}
+
+/// lands and mana rocks that can make a color of mana.
+pub fn produces(color: Color) -> CardPredicate {
+	Box::new(move |card| {
+		let is_source = card.card_types.contains(&CardType::Land) || card.card_types.contains(&CardType::Artifact);
+		is_source && mana_produced(card).contains(&color)
+	})
+}
+
+/// nonland cards with exactly this mana value.
+pub fn mana_value(mana_value: u8) -> CardPredicate {
+	Box::new(move |card| card.mana_value == mana_value && !card.card_types.contains(&CardType::Land))
+}
+
+/// "at least `count` cards that match `predicate`". When a card matches more than one
+/// requirement it can only count for one of them.
+pub struct CardRequirement {
+	pub predicate: CardPredicate,
+	pub count: u32,
+}
+
+impl CardRequirement {
+	pub fn new(predicate: CardPredicate, count: u32) -> CardRequirement {
+		CardRequirement { predicate, count }
+	}
+}
+
+/// How the game started, which changes how many cards you've seen by a given turn.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DrawContext {
+	/// the player going first skips their first draw.
+	pub on_the_play: bool,
+	/// London mulligans. You still see 7 cards and get to pick which ones go on the bottom, so
+	/// this only matters when you need more cards than you're allowed to keep plus what you've
+	/// drawn since.
+	pub mulligans: u32,
+}
+
+impl DrawContext {
+	/// how many cards you've seen by the start of your main phase on `turn` (turn 1 is your
+	/// first turn).
+	pub fn cards_seen(&self, turn: u32) -> u32 {
+		let draws = if self.on_the_play { turn.saturating_sub(1) } else { turn };
+		OPENING_HAND_SIZE as u32 + draws
+	}
+
+	fn cards_kept(&self) -> u32 {
+		(OPENING_HAND_SIZE as u32).saturating_sub(self.mulligans)
+	}
+
+	/// the most cards you can have by `turn`: what you kept plus what you've drawn since.
+	fn cards_held(&self, turn: u32) -> u32 {
+		self.cards_kept() + self.cards_seen(turn) - OPENING_HAND_SIZE as u32
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TurnProbability {
+	pub turn: u32,
+	pub probability: f64,
+}
+
+/// n choose k, as a float so it doesn't overflow for big decks.
+pub fn choose(n: u32, k: u32) -> f64 {
+	if k > n {
+		return 0.0;
+	}
+	let k = k.min(n - k);
+	(0..k).fold(1.0, |result, i| result * (n - i) as f64 / (i + 1) as f64)
+}
+
+/// the chance of drawing exactly `k` of the `successes` cards when drawing `draws` cards from a
+/// deck of `population`.
+pub fn hypergeometric(population: u32, successes: u32, draws: u32, k: u32) -> f64 {
+	if draws > population || successes > population {
+		return 0.0;
+	}
+	if k > draws || k > successes || draws - k > population - successes {
+		return 0.0;
+	}
+	choose(successes, k) * choose(population - successes, draws - k) / choose(population, draws)
+}
+
+/// the chance of drawing at least `k` of the `successes` cards.
+pub fn hypergeometric_at_least(population: u32, successes: u32, draws: u32, k: u32) -> f64 {
+	(k..=successes.min(draws)).map(|drawn| hypergeometric(population, successes, draws, drawn)).sum()
+}
+
+/// the chance of drawing at least `needed` from each group of cards. Groups are (size, needed)
+/// and can't overlap; everything else in the deck is in no group.
+pub fn multivariate_hypergeometric_at_least(population: u32, groups: &[(u32, u32)], draws: u32) -> f64 {
+	let requirements: Vec<u32> = groups.iter().map(|(_, needed)| *needed).collect();
+	let classes: Vec<(u32, u32)> = groups.iter().enumerate().map(|(i, (size, _))| (1 << i, *size)).collect();
+	class_probability(population, &classes, &requirements, draws)
+}
+
+/// the chance a deck has met every requirement by each turn from 1 to `turns`. Only the cards
+/// that start the game in the library count (not the sideboard, commanders or tokens).
+pub fn probability_by_turn(deck: &Deck, requirements: &[CardRequirement], context: DrawContext, turns: u32)
+	-> Vec<TurnProbability> {
+	let cards: Vec<&Card> = deck.cards.values()
+		.filter(|card| !card.is_token && !deck.is_commander(card.id))
+		.filter(|card| card.visibility_behavior.current_location != CardLocation::Sideboard)
+		.map(|card| card.card)
+		.collect();
+	let population = cards.len() as u32;
+
+	// cards that match the same requirements are interchangeable, so group them by which ones
+	// they match (as a bitmask).
+	let mut classes: Vec<(u32, u32)> = vec![];
+	for card in cards.iter() {
+		let mask = requirements.iter().enumerate()
+			.filter(|(_, requirement)| (requirement.predicate)(card))
+			.fold(0, |mask, (i, _)| mask | 1 << i);
+		if mask == 0 {
+			continue;
+		}
+		match classes.iter_mut().find(|(class_mask, _)| *class_mask == mask) {
+			Some((_, size)) => *size += 1,
+			None => classes.push((mask, 1)),
+		}
+	}
+	let needed: Vec<u32> = requirements.iter().map(|requirement| requirement.count).collect();
+	let total_needed: u32 = needed.iter().sum();
+
+	(1..=turns).map(|turn| {
+		let draws = context.cards_seen(turn).min(population);
+		let probability = match total_needed <= context.cards_held(turn) {
+			true => class_probability(population, &classes, &needed, draws),
+			false => 0.0,
+		};
+		TurnProbability { turn, probability }
+	}).collect()
+}
+
+/// sums the probability of every way of drawing from the classes that meets the requirements.
+fn class_probability(population: u32, classes: &[(u32, u32)], needed: &[u32], draws: u32) -> f64 {
+	let in_classes: u32 = classes.iter().map(|(_, size)| size).sum();
+	let others = population - in_classes;
+	let mut drawn = vec![0; classes.len()];
+	let total = sum_draws(classes, needed, draws, others, 0, &mut drawn);
+	total / choose(population, draws)
+}
+
+fn sum_draws(classes: &[(u32, u32)], needed: &[u32], draws: u32, others: u32, index: usize, drawn: &mut Vec<u32>) -> f64 {
+	let drawn_so_far: u32 = drawn[..index].iter().sum();
+	if index == classes.len() {
+		if !requirements_met(classes, needed, drawn) {
+			return 0.0;
+		}
+		return choose(others, draws - drawn_so_far);
+	}
+	let (_, size) = classes[index];
+	let mut total = 0.0;
+	for count in 0..=size.min(draws - drawn_so_far) {
+		drawn[index] = count;
+		let ways = choose(size, count);
+		total += ways * sum_draws(classes, needed, draws, others, index + 1, drawn);
+	}
+	drawn[index] = 0;
+	total
+}
+
+/// whether the cards drawn can be split up to cover every requirement, with each card only
+/// counting once. By Hall's theorem that's true when every set of requirements has at least as
+/// many drawn cards matching one of them as they need between them.
+fn requirements_met(classes: &[(u32, u32)], needed: &[u32], drawn: &[u32]) -> bool {
+	(1..1u32 << needed.len()).all(|subset| {
+		let need: u32 = needed.iter().enumerate()
+			.filter(|(i, _)| subset & 1 << i != 0)
+			.map(|(_, needed)| needed)
+			.sum();
+		let have: u32 = classes.iter().zip(drawn.iter())
+			.filter(|((mask, _), _)| mask & subset != 0)
+			.map(|(_, drawn)| drawn)
+			.sum();
+		have >= need
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::deck::CardListItem;
+	use crate::state_manager::Player;
+
+	fn close(a: f64, b: f64) -> bool {
+		(a - b).abs() < 1e-9
+	}
+
+	#[test]
+	fn hypergeometric_matches_the_textbook(){
+		// 4 copies in 60 cards, 7 card hand: about 39.95% to have at least one.
+		assert!(close(hypergeometric_at_least(60, 4, 7, 1), 1.0 - choose(56, 7) / choose(60, 7)));
+		assert!((hypergeometric_at_least(60, 4, 7, 1) - 0.3995).abs() < 0.0001);
+		let total: f64 = (0..=4).map(|k| hypergeometric(60, 4, 7, k)).sum();
+		assert!(close(total, 1.0));
+		// you can't draw more than you drew, more than there are, or fewer than the rest allow.
+		assert_eq!(hypergeometric(60, 4, 1, 2), 0.0);
+		assert_eq!(hypergeometric(60, 4, 7, 5), 0.0);
+		assert_eq!(hypergeometric(10, 8, 5, 2), 0.0);
+	}
+
+	#[test]
+	fn multivariate_with_one_group_is_just_hypergeometric(){
+		assert!(close(
+			multivariate_hypergeometric_at_least(60, &[(24, 3)], 9),
+			hypergeometric_at_least(60, 24, 9, 3)
+		));
+		let both = multivariate_hypergeometric_at_least(40, &[(17, 2), (4, 1)], 9);
+		assert!(both < hypergeometric_at_least(40, 17, 9, 2));
+		assert!(both < hypergeometric_at_least(40, 4, 9, 1));
+	}
+
+	#[test]
+	fn deck_probabilities_go_up_every_turn_and_the_draw_helps(){
+		let vec = vec![
+			CardListItem("Plains".to_string(), 17),
+			CardListItem("Mentor of the Meek".to_string(), 4),
+			CardListItem("Dissimulate".to_string(), 4),
+			CardListItem("Elspeth Tirel".to_string(), 15),
+		];
+		let deck = Deck::new(Player::new("Me"), &vec, &vec![]).unwrap();
+		let requirements = vec![
+			CardRequirement::new(named("Plains"), 2),
+			CardRequirement::new(mana_value(2), 1),
+		];
+		let on_the_play = probability_by_turn(&deck, &requirements, DrawContext { on_the_play: true, mulligans: 0 }, 3);
+		let on_the_draw = probability_by_turn(&deck, &requirements, DrawContext { on_the_play: false, mulligans: 0 }, 3);
+		assert_eq!(on_the_play.len(), 3);
+		assert!(on_the_play[0].probability < on_the_play[2].probability);
+		assert!(close(on_the_play[1].probability, on_the_draw[0].probability));
+		assert!(close(
+			on_the_play[0].probability,
+			multivariate_hypergeometric_at_least(40, &[(17, 2), (4, 1)], 7)
+		));
+	}
+
+	#[test]
+	fn turn_zero_is_just_the_opening_hand(){
+		assert_eq!(DrawContext { on_the_play: true, mulligans: 0 }.cards_seen(0), 7);
+		assert_eq!(DrawContext { on_the_play: true, mulligans: 0 }.cards_seen(1), 7);
+		assert_eq!(DrawContext { on_the_play: false, mulligans: 0 }.cards_seen(1), 8);
+	}
+
+	#[test]
+	fn cards_matching_two_requirements_only_count_once(){
+		let vec = vec![
+			CardListItem("Plains".to_string(), 2),
+			CardListItem("Elspeth Tirel".to_string(), 1),
+		];
+		let deck = Deck::new(Player::new("Me"), &vec, &vec![]).unwrap();
+		// both Plains make white mana, but you'd need 3 cards to have 2 Plains AND 2 white sources.
+		let requirements = vec![
+			CardRequirement::new(named("Plains"), 2),
+			CardRequirement::new(produces(Color::W), 2),
+		];
+		let odds = probability_by_turn(&deck, &requirements, DrawContext::default(), 1);
+		assert_eq!(odds[0].probability, 0.0);
+		// mulliganing to one means the second land has to be drawn later.
+		let requirements = vec![CardRequirement::new(has_type(CardType::Land), 2)];
+		let odds = probability_by_turn(&deck, &requirements, DrawContext { on_the_play: true, mulligans: 6 }, 2);
+		assert_eq!(odds[0].probability, 0.0);
+		assert!(odds[1].probability > 0.0);
+	}
+}
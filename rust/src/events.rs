@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+use crate::card::CardId;
+use crate::reducers::{CurrentEvent, EventKind};
+
+/// Identifies a subscription so it can be cancelled later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SubscriptionId(u64);
+
+/// A card that wants to know whenever certain kinds of events happen, like Dragon's Rage
+/// Channeler wanting to hear about spells being cast.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subscription {
+	pub id: SubscriptionId,
+	/// the player who controls the card.
+	pub player_name: String,
+	pub card_id: CardId,
+	pub kinds: Vec<EventKind>,
+}
+
+/// An event that's been processed, along with every subscription that was interested in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessedEvent {
+	pub event: CurrentEvent,
+	pub subscribers: Vec<Subscription>,
+}
+
+/// Events waiting to happen, in the order they happen in, plus a log of the ones that already
+/// have. An action that causes a bunch of events queues them all up and they get processed one
+/// after another, so none of them get lost.
+#[derive(Debug, Clone, Default)]
+pub struct EventQueue {
+	pending: VecDeque<CurrentEvent>,
+	/// every event processed this game, oldest first.
+	pub log: Vec<CurrentEvent>,
+	/// the events the last action dispatched caused, in order.
+	pub last_processed: Vec<ProcessedEvent>,
+	subscriptions: Vec<Subscription>,
+	next_subscription_id: u64,
+}
+
+impl EventQueue {
+	/// queues an event up behind the ones already waiting.
+	pub fn queue(&mut self, event: CurrentEvent) {
+		self.pending.push_back(event);
+	}
+
+	/// queues a bunch of events to run one after another.
+	pub fn queue_all(&mut self, events: Vec<CurrentEvent>) {
+		self.pending.extend(events);
+	}
+
+	/// the events that haven't been processed yet, next one first.
+	pub fn pending(&self) -> impl Iterator<Item = &CurrentEvent> {
+		self.pending.iter()
+	}
+
+	/// takes the next event off the queue, logs it and works out who's interested in it.
+	pub fn process_next(&mut self) -> Option<ProcessedEvent> {
+		let event = self.pending.pop_front()?;
+		self.log.push(event.clone());
+		let subscribers = self.subscribers(EventKind::from(&event)).cloned().collect();
+		Some(ProcessedEvent { event, subscribers })
+	}
+
+	/// processes everything in the queue, in order. Returns the same thing as last_processed.
+	pub fn process_all(&mut self) -> Vec<ProcessedEvent> {
+		let mut processed = vec![];
+		while let Some(event) = self.process_next() {
+			processed.push(event);
+		}
+		self.last_processed = processed.clone();
+		processed
+	}
+
+	/// the most recently processed event, if there's been one.
+	pub fn latest(&self) -> Option<&CurrentEvent> {
+		self.log.last()
+	}
+
+	/// a card starts listening for some kinds of events.
+	pub fn subscribe(&mut self, player_name: &str, card_id: CardId, kinds: Vec<EventKind>) -> SubscriptionId {
+		let id = SubscriptionId(self.next_subscription_id);
+		self.next_subscription_id += 1;
+		self.subscriptions.push(Subscription { id, player_name: player_name.to_string(), card_id, kinds });
+		id
+	}
+
+	pub fn unsubscribe(&mut self, id: SubscriptionId) {
+		self.subscriptions.retain(|subscription| subscription.id != id);
+	}
+
+	/// drops every subscription a card has, like when it leaves the battlefield.
+	pub fn unsubscribe_card(&mut self, card_id: CardId) {
+		self.subscriptions.retain(|subscription| subscription.card_id != card_id);
+	}
+
+	/// every subscription listening for a kind of event, oldest first.
+	pub fn subscribers(&self, kind: EventKind) -> impl Iterator<Item = &Subscription> {
+		self.subscriptions.iter().filter(move |subscription| subscription.kinds.contains(&kind))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::reducers::EventSource;
+
+	#[test]
+	fn events_get_processed_in_order(){
+		let mut queue = EventQueue::default();
+		queue.queue(CurrentEvent::CardDrawn(EventSource::NaturalProgression));
+		queue.queue_all(vec![
+			CurrentEvent::CardPlacedInGraveyard(EventSource::NaturalProgression),
+			CurrentEvent::LibraryShuffled(EventSource::NaturalProgression),
+		]);
+		assert_eq!(queue.pending().count(), 3);
+		let processed = queue.process_all();
+		assert_eq!(processed.len(), 3);
+		assert!(matches!(processed[0].event, CurrentEvent::CardDrawn(_)));
+		assert!(matches!(processed[2].event, CurrentEvent::LibraryShuffled(_)));
+		assert_eq!(queue.log.len(), 3);
+		assert_eq!(queue.latest(), Some(&CurrentEvent::LibraryShuffled(EventSource::NaturalProgression)));
+		assert!(queue.process_next().is_none());
+	}
+
+	#[test]
+	fn subscribers_only_hear_about_what_they_asked_for(){
+		let mut queue = EventQueue::default();
+		let card_id = CardId::generate();
+		let graveyard = queue.subscribe("Andrew", card_id, vec![EventKind::CardPlacedInGraveyard]);
+		queue.subscribe("Rory", CardId::generate(), vec![EventKind::CardDrawn, EventKind::CardPlacedInGraveyard]);
+		queue.queue(CurrentEvent::CardPlacedInGraveyard(EventSource::NaturalProgression));
+		queue.queue(CurrentEvent::CardDrawn(EventSource::NaturalProgression));
+		let processed = queue.process_all();
+		assert_eq!(processed[0].subscribers.len(), 2);
+		assert_eq!(processed[0].subscribers[0].id, graveyard);
+		assert_eq!(processed[1].subscribers.len(), 1);
+		assert_eq!(processed[1].subscribers[0].player_name, "Rory");
+
+		queue.unsubscribe_card(card_id);
+		assert_eq!(queue.subscribers(EventKind::CardPlacedInGraveyard).count(), 1);
+	}
+}
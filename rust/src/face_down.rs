@@ -0,0 +1,103 @@
+use std::sync::OnceLock;
+use regex::Regex;
+use crate::card::CardType;
+use crate::cost::{Cost, parse_costs_better};
+
+/// The different ways a card can end up face down on the battlefield. They're all 2/2 creatures
+/// with no name, no types and no text, but disguised and cloaked ones also have ward {2}.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FaceDownKind {
+	/// cast face down for {3} using its morph (or megamorph) ability.
+	Morph,
+	/// cast face down for {3} using its disguise ability.
+	Disguise,
+	/// put onto the battlefield face down from the top of the library.
+	Manifest,
+	/// manifest, but with ward {2}.
+	Cloak,
+}
+
+impl FaceDownKind {
+	pub fn has_ward(&self) -> bool {
+		*self == FaceDownKind::Disguise || *self == FaceDownKind::Cloak
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FaceDownError {
+	NotFaceDown,
+	/// manifested/cloaked noncreature cards without morph or disguise can't be turned face up.
+	CantBeTurnedFaceUp,
+	/// only permanents get turned face up; not face-down spells on the stack.
+	NotOnBattlefield,
+}
+
+/// what it costs to cast a spell face down.
+pub fn face_down_spell_cost() -> Cost {
+	parse_costs_better("{3}")
+}
+
+/// whether a card can be cast face down as `kind`: morph needs a morph or megamorph ability and
+/// disguise needs a disguise ability. Manifested and cloaked cards are never cast.
+pub fn can_be_cast_face_down(description: &str, kind: FaceDownKind) -> bool {
+	static ABILITY: OnceLock<Regex> = OnceLock::new();
+	let ability = ABILITY.get_or_init(|| Regex::new(r"(?m)^(Morph|Megamorph|Disguise) \{").unwrap());
+	ability.captures_iter(description).any(|captures| matches!(
+		(&captures[1], kind),
+		("Morph" | "Megamorph", FaceDownKind::Morph) | ("Disguise", FaceDownKind::Disguise)
+	))
+}
+
+/// finds the cost to turn a card face up from its morph/megamorph/disguise ability, like
+/// "Disguise {5}{R}".
+pub fn parse_turn_face_up_cost(description: &str) -> Option<Cost> {
+	let re = Regex::new(r"(?m)^(?:Morph|Megamorph|Disguise) ((?:\{[^}]+})+)").unwrap();
+	let captures = re.captures(description)?;
+	Some(parse_costs_better(&captures[1]))
+}
+
+/// What a player can see about a card. Opponents can't see what a face-down card really is, so
+/// the view of a face-down card doesn't have a name and is just a 2/2 creature.
+#[derive(Debug, PartialEq)]
+pub struct CardView {
+	pub name: Option<String>,
+	pub card_types: Vec<CardType>,
+	pub subtypes: Vec<String>,
+	pub keywords: Vec<String>,
+	pub power: i32,
+	pub toughness: i32,
+	pub is_face_down: bool,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::colors::Color;
+
+	#[test]
+	fn parses_disguise_cost(){
+		let cost = parse_turn_face_up_cost("Prowess, haste\nDisguise {5}{R}").unwrap();
+		assert_eq!(cost.cost.get(&Color::C), Some(&5));
+		assert_eq!(cost.cost.get(&Color::R), Some(&1));
+	}
+
+	#[test]
+	fn parses_megamorph_cost(){
+		let cost = parse_turn_face_up_cost("Lifelink\nMegamorph {2}{W}").unwrap();
+		assert_eq!(cost.cost.get(&Color::W), Some(&1));
+	}
+
+	#[test]
+	fn only_morph_and_disguise_cards_get_cast_face_down(){
+		assert!(can_be_cast_face_down("Lifelink\nMegamorph {2}{W}", FaceDownKind::Morph));
+		assert!(!can_be_cast_face_down("Lifelink\nMegamorph {2}{W}", FaceDownKind::Disguise));
+		assert!(can_be_cast_face_down("Prowess, haste\nDisguise {5}{R}", FaceDownKind::Disguise));
+		assert!(!can_be_cast_face_down("Prowess, haste\nDisguise {5}{R}", FaceDownKind::Manifest));
+		assert!(!can_be_cast_face_down("Destroy target creature.", FaceDownKind::Morph));
+	}
+
+	#[test]
+	fn cards_without_morph_dont_have_a_face_up_cost(){
+		assert_eq!(parse_turn_face_up_cost("Destroy target creature."), None);
+	}
+}
@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::Serialize;
+use crate::card::{CardId, CardLocation, CardType};
+use crate::cost::{mana_sources, pay_cost, ManaSource};
+use crate::deck::Deck;
+use crate::rng::GameRng;
+
+/// How the simulator decides what to do each turn.
+pub trait PlayPolicy {
+	/// which land in the hand to play, if any.
+	fn choose_land(&self, deck: &Deck) -> Option<CardId>;
+	/// which of the spells it can afford right now to cast next, if any.
+	fn choose_spell(&self, deck: &Deck, castable: &[CardId]) -> Option<CardId>;
+}
+
+/// plays the first land it drew, then keeps casting the most expensive spell it can pay for.
+pub struct CurveOut;
+
+impl PlayPolicy for CurveOut {
+	fn choose_land(&self, deck: &Deck) -> Option<CardId> {
+		deck.hand.iter().find(|card_id| deck.cards[*card_id].card.card_types.contains(&CardType::Land)).cloned()
+	}
+
+	fn choose_spell(&self, deck: &Deck, castable: &[CardId]) -> Option<CardId> {
+		castable.iter().max_by_key(|card_id| deck.cards[*card_id].card.mana_value).cloned()
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct GoldfishOptions {
+	pub games: u32,
+	/// how many turns each game goes.
+	pub turns: u32,
+	/// every game comes from this one seed, so running it again gives the same report.
+	pub seed: u64,
+	pub on_the_play: bool,
+	/// a card to find the first turn it could have been cast in each game.
+	pub tracked_card: Option<String>,
+}
+
+/// What happened over all the games. The per-turn lists start at turn 1.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GoldfishReport {
+	pub games: u32,
+	pub average_lands_in_play: Vec<f64>,
+	/// how much mana there was to spend at the start of each turn's main phase (after the land drop).
+	pub average_mana_available: Vec<f64>,
+	pub average_spells_cast: Vec<f64>,
+	/// turn -> how many games the tracked card was first castable on that turn.
+	pub first_castable_turn: BTreeMap<u32, u32>,
+	/// games where the tracked card never got cast-able.
+	pub never_castable: u32,
+}
+
+/// plays a bunch of solo games with a deck and reports how they went. The deck itself isn't
+/// touched; each game starts from the deck's registered list.
+pub fn goldfish(deck: &Deck, policy: &dyn PlayPolicy, options: &GoldfishOptions) -> GoldfishReport {
+	let turns = options.turns as usize;
+	let mut lands = vec![0u64; turns];
+	let mut mana = vec![0u64; turns];
+	let mut spells = vec![0u64; turns];
+	let mut first_castable_turn = BTreeMap::new();
+	let mut never_castable = 0;
+	let mut rng = GameRng::new(options.seed);
+	let mut deck = deck.clone();
+	// working these out means running regexes over the card text, so only do it once per card.
+	let sources: HashMap<CardId, Vec<ManaSource>> = deck.cards.iter()
+		.map(|(card_id, card)| (*card_id, mana_sources(card.card)))
+		.collect();
+	// reused every turn of every game instead of being allocated again each time.
+	let mut pool: Vec<ManaSource> = vec![];
+	let mut castable: Vec<CardId> = vec![];
+
+	for _ in 0..options.games {
+		deck.restore_registered_list();
+		deck.draw_opening_hand(&mut rng);
+		let mut first_castable = None;
+		for turn in 0..turns {
+			if turn > 0 || !options.on_the_play {
+				deck.draw_card();
+			}
+			if let Some(land) = policy.choose_land(&deck) {
+				deck.move_card(land, CardLocation::Hand, CardLocation::Battlefield).unwrap();
+			}
+			pool.clear();
+			pool.extend(deck.battlefield.iter().flat_map(|card_id| sources[card_id].iter().cloned()));
+			lands[turn] += deck.battlefield.iter()
+				.filter(|card_id| deck.cards[*card_id].card.card_types.contains(&CardType::Land))
+				.count() as u64;
+			mana[turn] += pool.len() as u64;
+
+			if let (None, Some(tracked)) = (first_castable, &options.tracked_card) {
+				let castable = deck.hand.iter()
+					.map(|card_id| deck.cards[card_id].card)
+					.any(|card| card.name == *tracked && pay_cost(&card.mana_cost, &pool).is_some());
+				if castable {
+					first_castable = Some(turn as u32 + 1);
+				}
+			}
+
+			loop {
+				castable.clear();
+				castable.extend(deck.hand.iter()
+					.filter(|card_id| !deck.cards[*card_id].card.card_types.contains(&CardType::Land))
+					.filter(|card_id| pay_cost(&deck.cards[*card_id].card.mana_cost, &pool).is_some()));
+				let spell = match policy.choose_spell(&deck, &castable) {
+					Some(spell) => spell,
+					None => break,
+				};
+				let card = deck.cards[&spell].card;
+				let mut tapped = pay_cost(&card.mana_cost, &pool).unwrap();
+				tapped.sort();
+				for i in tapped.iter().rev() {
+					pool.remove(*i);
+				}
+				let is_permanent = !card.card_types.contains(&CardType::Instant) && !card.card_types.contains(&CardType::Sorcery);
+				if is_permanent {
+					deck.move_card(spell, CardLocation::Hand, CardLocation::Battlefield).unwrap();
+					// mana rocks can be tapped the turn they come down.
+					if card.card_types.contains(&CardType::Artifact) && !card.card_types.contains(&CardType::Creature) {
+						pool.extend(sources[&spell].iter().cloned());
+					}
+				} else {
+					deck.move_card(spell, CardLocation::Hand, CardLocation::Graveyard).unwrap();
+				}
+				spells[turn] += 1;
+			}
+		}
+		match first_castable {
+			Some(turn) => *first_castable_turn.entry(turn).or_insert(0) += 1,
+			None if options.tracked_card.is_some() => never_castable += 1,
+			None => {}
+		}
+	}
+
+	let average = |totals: Vec<u64>| totals.iter().map(|total| *total as f64 / options.games.max(1) as f64).collect();
+	GoldfishReport {
+		games: options.games,
+		average_lands_in_play: average(lands),
+		average_mana_available: average(mana),
+		average_spells_cast: average(spells),
+		first_castable_turn,
+		never_castable,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::card_db::get_card_db;
+	use crate::deck::CardListItem;
+	use crate::state_manager::Player;
+
+	fn red_deck() -> Deck {
+		let vec = vec![
+			CardListItem("Mountain".to_string(), 24),
+			CardListItem("Dragon's Rage Channeler".to_string(), 12),
+			CardListItem("Hordeling Outburst".to_string(), 12),
+			CardListItem("Siege-Gang Commander".to_string(), 8),
+			CardListItem("Sol Ring".to_string(), 4),
+		];
+		Deck::new(Player::new("Me"), &vec, &vec![]).unwrap()
+	}
+
+	#[test]
+	fn goldfishing_is_reproducible_and_sensible(){
+		let deck = red_deck();
+		let options = GoldfishOptions {
+			games: 300,
+			turns: 5,
+			seed: 8,
+			on_the_play: true,
+			tracked_card: Some("Siege-Gang Commander".to_string()),
+		};
+		let report = goldfish(&deck, &CurveOut, &options);
+		assert_eq!(report, goldfish(&deck, &CurveOut, &options));
+		assert_eq!(report.average_lands_in_play.len(), 5);
+		assert!(report.average_lands_in_play[0] <= 1.0);
+		assert!(report.average_lands_in_play[4] > report.average_lands_in_play[0]);
+		assert!(report.average_mana_available[4] > report.average_mana_available[0]);
+		// you need five mana, and Sol Ring's the only way to get there before turn 5.
+		assert!(report.first_castable_turn.keys().all(|turn| *turn >= 2));
+		let tracked: u32 = report.first_castable_turn.values().sum();
+		assert_eq!(tracked + report.never_castable, 300);
+		assert!(deck.hand.is_empty());
+	}
+
+	/// 100,000 games of 7 turns. Takes about 4 seconds in a release build
+	/// (`cargo test --release -- --ignored goldfishing_a_hundred_thousand_games`).
+	#[test]
+	#[ignore]
+	fn goldfishing_a_hundred_thousand_games(){
+		let deck = red_deck();
+		let options = GoldfishOptions {
+			games: 100_000,
+			turns: 7,
+			seed: 42,
+			on_the_play: true,
+			tracked_card: Some("Siege-Gang Commander".to_string()),
+		};
+		let started = std::time::Instant::now();
+		let report = goldfish(&deck, &CurveOut, &options);
+		println!("goldfished {} games in {:?}", options.games, started.elapsed());
+		let tracked: u32 = report.first_castable_turn.values().sum();
+		assert_eq!(tracked + report.never_castable, 100_000);
+		assert!(report.average_lands_in_play[6] > report.average_lands_in_play[0]);
+	}
+}
@@ -0,0 +1,146 @@
+use regex::Regex;
+use crate::card::Card;
+
+/// What it costs to activate a loyalty ability, i.e. the bit in the brackets. [+2], [−X], [0], etc.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoyaltyCost {
+	Plus(u32),
+	Minus(u32),
+	PlusX,
+	MinusX,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoyaltyAbility {
+	pub cost: LoyaltyCost,
+	/// what the ability does (everything after the colon).
+	pub text: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LoyaltyError {
+	/// the card doesn't have loyalty abilities.
+	NotAPlaneswalker,
+	NotOnBattlefield,
+	/// only one loyalty ability per planeswalker per turn.
+	AlreadyActivatedThisTurn,
+	/// loyalty abilities can only be activated when you could cast a sorcery.
+	NotSorcerySpeed,
+	/// you can't remove more loyalty counters than the planeswalker has.
+	NotEnoughLoyalty,
+	NoSuchAbility,
+}
+
+/// parses the loyalty abilities out of a card's text. mtgjson uses a real minus sign (−) in the
+/// brackets but we accept a hyphen too.
+pub fn parse_loyalty_abilities(description: &str) -> Vec<LoyaltyAbility> {
+	let re = Regex::new(r"(?m)^\[([+−-]?)(\d+|X)\]: (.+)$").unwrap();
+	re.captures_iter(description).map(|captures| {
+		let is_minus = &captures[1] == "−" || &captures[1] == "-";
+		let cost = match (&captures[2], is_minus) {
+			("X", true) => LoyaltyCost::MinusX,
+			("X", false) => LoyaltyCost::PlusX,
+			(amount, true) => LoyaltyCost::Minus(amount.parse().unwrap()),
+			(amount, false) => LoyaltyCost::Plus(amount.parse().unwrap()),
+		};
+		LoyaltyAbility { cost, text: captures[3].to_string() }
+	}).collect()
+}
+
+/// How a planeswalker's loyalty works. The loyalty itself is just loyalty counters on the RealCard;
+/// this keeps track of what it starts with and what abilities it has.
+#[derive(Debug, Default, Clone)]
+pub struct LoyaltyBehavior {
+	/// how many loyalty counters it enters the battlefield with. (X is 0 here.)
+	pub starting_loyalty: u32,
+	pub abilities: Vec<LoyaltyAbility>,
+	activated_this_turn: bool,
+}
+
+impl LoyaltyBehavior {
+	/// returns None if the card doesn't have a loyalty (so it's not a planeswalker).
+	pub fn new(card: &Card) -> Option<LoyaltyBehavior> {
+		let loyalty = card.loyalty.as_ref()?;
+		Some(LoyaltyBehavior {
+			starting_loyalty: loyalty.parse().unwrap_or(0),
+			abilities: parse_loyalty_abilities(&card.description),
+			activated_this_turn: false,
+		})
+	}
+
+	pub fn activated_this_turn(&self) -> bool {
+		self.activated_this_turn
+	}
+
+	/// records that a loyalty ability was activated; if it can't be activated right now, it says why.
+	/// Returns how the loyalty counters change: positive to add, negative to remove.
+	pub fn activate(&mut self, ability_index: usize, x: u32, current_loyalty: u32, can_act_at_sorcery_speed: bool)
+		-> Result<i64, LoyaltyError> {
+		let ability = self.abilities.get(ability_index).ok_or(LoyaltyError::NoSuchAbility)?;
+		if self.activated_this_turn {
+			return Err(LoyaltyError::AlreadyActivatedThisTurn);
+		}
+		if !can_act_at_sorcery_speed {
+			return Err(LoyaltyError::NotSorcerySpeed);
+		}
+		let change = match ability.cost {
+			LoyaltyCost::Plus(amount) => amount as i64,
+			LoyaltyCost::PlusX => x as i64,
+			LoyaltyCost::Minus(amount) => -(amount as i64),
+			LoyaltyCost::MinusX => -(x as i64),
+		};
+		if change < 0 && (-change) as u32 > current_loyalty {
+			return Err(LoyaltyError::NotEnoughLoyalty);
+		}
+		self.activated_this_turn = true;
+		Ok(change)
+	}
+
+	/// lets the planeswalker activate a loyalty ability again. Call this when its controller's turn starts.
+	pub fn reset_for_new_turn(&mut self) {
+		self.activated_this_turn = false;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ELSPETH: &str = "[+2]: You gain 1 life for each creature you control.\n\
+		[−2]: Create three 1/1 white Soldier creature tokens.\n\
+		[−5]: Destroy all other permanents except for lands and tokens.";
+
+	#[test]
+	fn parses_elspeths_abilities(){
+		let abilities = parse_loyalty_abilities(ELSPETH);
+		assert_eq!(abilities.len(), 3);
+		assert_eq!(abilities[0].cost, LoyaltyCost::Plus(2));
+		assert_eq!(abilities[1].cost, LoyaltyCost::Minus(2));
+		assert_eq!(abilities[1].text, "Create three 1/1 white Soldier creature tokens.");
+		assert_eq!(abilities[2].cost, LoyaltyCost::Minus(5));
+	}
+
+	#[test]
+	fn parses_zero_and_x_abilities(){
+		let abilities = parse_loyalty_abilities("[0]: Draw a card.\n[−X]: Destroy target creature with mana value X.");
+		assert_eq!(abilities[0].cost, LoyaltyCost::Plus(0));
+		assert_eq!(abilities[1].cost, LoyaltyCost::MinusX);
+	}
+
+	#[test]
+	fn loyalty_abilities_are_once_per_turn_at_sorcery_speed(){
+		let card = Card {
+			description: ELSPETH.to_string(),
+			loyalty: Some("4".to_string()),
+			..Default::default()
+		};
+		let mut loyalty = LoyaltyBehavior::new(&card).unwrap();
+		assert_eq!(loyalty.starting_loyalty, 4);
+		assert_eq!(loyalty.activate(0, 0, 4, false), Err(LoyaltyError::NotSorcerySpeed));
+		assert_eq!(loyalty.activate(2, 0, 4, true), Err(LoyaltyError::NotEnoughLoyalty));
+		assert_eq!(loyalty.activate(1, 0, 4, true), Ok(-2));
+		assert_eq!(loyalty.activate(0, 0, 2, true), Err(LoyaltyError::AlreadyActivatedThisTurn));
+		loyalty.reset_for_new_turn();
+		assert_eq!(loyalty.activate(0, 0, 2, true), Ok(2));
+	}
+}
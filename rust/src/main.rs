@@ -4,12 +4,26 @@
 #![allow(unused)]
 mod colors;
 mod cost;
+mod counters;
+mod loyalty;
+mod face_down;
 mod card;
+mod commander;
 mod card_db;
 mod deck;
+mod deck_list;
+mod deck_stats;
+mod deck_validation;
+mod draw_odds;
+mod events;
+mod goldfish;
+mod token;
+mod triggers;
+mod stack;
 mod state_manager;
 mod selectors;
 mod reducers;
+mod rng;
 mod example_decks;
 
 
@@ -31,14 +45,13 @@ async fn main() {
 	let store = Store::new_with_state(reducer, gs);
 	let blakes_cards = build_blakes_example_deck();
 	let blakes_sideboard = &vec![];
-	let mut blakes_deck = Deck::new(Player{name: "Blake".to_string()}, &blakes_cards, &blakes_sideboard).unwrap();
+	let blakes_deck = Deck::new(Player::new("Blake"), &blakes_cards, &blakes_sideboard).unwrap();
 
 
 	println!("{:#?}", blakes_deck.get_library_card_names_in_order());
-	blakes_deck.shuffle_library();
-	println!("{:#?}", blakes_deck.get_library_card_names_in_order());
-	blakes_deck.shuffle_library();
-	println!("{:#?}", blakes_deck.get_library_card_names_in_order());
+	store.dispatch(Action::AddDeck(Box::new(blakes_deck))).await;
+	// shuffled with the game's rng, so the game can be replayed from its seed.
+	store.dispatch(Action::ShuffleLibrary("Blake".to_string())).await;
 
 	store.subscribe(|state: &GameState | println!("New state: {:#?}", state)).await;
 
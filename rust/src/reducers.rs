@@ -1,25 +1,39 @@
-use crate::state_manager::{GameState, Player};
+use strum_macros::EnumDiscriminants;
+use crate::card::{CardId, CardLocation};
+use crate::counters::CounterType;
+use crate::deck::{CardListItem, Deck, SideboardError};
+use crate::stack::{CastError, PriorityError, SpellChoices};
+use crate::state_manager::{GameRules, GameState, Player, TurnPhase};
+use crate::token::TokenDefinition;
+use crate::triggers::TriggerError;
 
 #[derive(Debug, Clone, PartialEq)]
 /// Event Sources are things that cause an event to take place. For Example, if a creature gets killed
-/// by an instant like murder, that'd be EventSource::Card("player_1", "Murder")
+/// by an instant like murder, that'd be EventSource::Card("player_1", murders_card_id)
 pub enum EventSource{
-	/// A card that caused the event to take place. Args are: (player_name, card_name)
-	Card(String, String),
+	/// A card that caused the event to take place. Args are: (player_name, card_id)
+	Card(String, CardId),
 	/// a thing that happens as a natural result of the game like CardRegeneratedLife (which happens
 	/// in the upkeep phase).
 	NaturalProgression,
-	/// vector of tuples of (player_names, card_names). Like if multiple creatures are blocking
-	Damage(Vec<(String, String)>),
+	/// vector of tuples of (player_names, card_ids). Like if multiple creatures are blocking
+	Damage(Vec<(String, CardId)>),
 	/// playerName
 	Player(Player),
 }
 
 /// some cards will have to subscribe to current events because theyll have actions that happen
 /// when certain events take place. like some cards deal damage when a card enters the graveyard, etc.
-#[derive(Debug, Default, Clone, PartialEq)]
+/// EventKind is the same list without the sources, for subscribing to (see EventQueue::subscribe).
+#[derive(Debug, Default, Clone, PartialEq, EnumDiscriminants)]
+#[strum_discriminants(name(EventKind), derive(Hash))]
 pub enum CurrentEvent{
 	CardRevealedOnLibrary(EventSource),
+	CardPutOnTopOfLibrary(EventSource),
+	CardPutOnBottomOfLibrary(EventSource),
+	CardPutIntoHand(EventSource),
+	PlayerMulliganed(EventSource),
+	PlayerKeptHand(EventSource),
 	CardPlacedFaceDown(EventSource),
 	CardTurnedFaceUp(EventSource),
 
@@ -42,35 +56,127 @@ pub enum CurrentEvent{
 	CardDeclaredAsAttacker(EventSource),
 	CardDeclaredAsBlocker(EventSource),
 
+	/// counters were put on the card or player in the EventSource.
+	CounterPlaced(EventSource),
+	/// counters were removed from the card or player in the EventSource.
+	CounterRemoved(EventSource),
+
 	CardDealtDamage(EventSource),
 	PlayerDealtDamage(EventSource),
 	PlayerKilled(EventSource),
+	/// someone won the game; the source is whatever made them win.
+	PlayerWon(EventSource),
 	CardRegeneratedLife(EventSource),
 	PlayerGainedLife(EventSource),
 
 	LibraryShuffled(EventSource),
 	GraveyardShuffledIntoLibrary(EventSource),
+	/// a step or phase of the turn is starting, for "at the beginning of your upkeep" and such.
+	BeginningOfStep(TurnPhase),
 	GraveyardExiled(EventSource),
 	None,
 	#[default]
 	NewGame
 }
 
-#[derive(Debug)]
+/// Why an action got turned down. It ends up in state.rejected_action so the UI can tell the
+/// player what went wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionError {
+	Sideboard(SideboardError),
+	Trigger(TriggerError),
+	Cast(CastError),
+	Priority(PriorityError),
+}
+
+#[derive(Debug, Clone)]
 pub enum Action {
+	/// queues up an event to be processed.
 	UpdateCurrentEvent{event: CurrentEvent},
 	ProgressMetaGamePhase,
 	RegressMetaGamePhase,
 	ProgressTurn,
+	/// moves on to the next step of the turn. Ignored until everyone's passed priority in a row
+	/// with an empty stack.
 	ProgressTurnPhase,
-	AddPlayer(String)
+	AddPlayer(String),
+	/// adds a player's deck to the game. If a deck for that player is already there, it gets replaced.
+	/// Its cards get new ids from the game.
+	AddDeck(Box<Deck>),
+	/// creates a token on the battlefield for a player.
+	CreateToken{player_name: String, definition: TokenDefinition},
+	/// put counters on a player or card. Cards are looked up in their owner's deck.
+	PutCounters{target: EventSource, counter: CounterType, amount: u32},
+	/// remove counters from a player or card.
+	RemoveCounters{target: EventSource, counter: CounterType, amount: u32},
+	/// sets the format's rules (starting life, free mulligans, etc.). Do this before adding players.
+	SetGameRules(GameRules),
+	/// sideboarding between games of a match. If the swap isn't legal in the game's format
+	/// nothing changes and the reason ends up in state.rejected_action.
+	SwapSideboardCards{player_name: String, take_out: Vec<CardListItem>, bring_in: Vec<CardListItem>},
+	/// puts a player's deck back the way it was registered, like at the end of a match.
+	RestoreRegisteredDeck(String),
+	/// shuffles a player's library with the game's rng.
+	ShuffleLibrary(String),
+	/// a player draws a card.
+	DrawCard(String),
+	/// checks state-based actions, like players losing from drawing off an empty library.
+	CheckStateBasedActions,
+	/// moves one of a player's cards from one zone to another. Ignored if it isn't in `from`.
+	MoveCard{player_name: String, card_id: CardId, from: CardLocation, to: CardLocation},
+	/// a player casts a spell. Ignored if they can't (see GameState::cast_spell for why not).
+	CastSpell{player_name: String, card_id: CardId, choices: SpellChoices},
+	/// the player with priority passes.
+	PassPriority(String),
+	/// a player picks the order their simultaneous triggers go on the stack in (see
+	/// GameState::order_triggers). They go on the stack once everybody who has to has picked.
+	/// If the order doesn't make sense nothing changes and the reason ends up in
+	/// state.rejected_action.
+	OrderTriggers{player_name: String, order: Vec<usize>},
+	/// shuffles a player's graveyard into their library.
+	ShuffleGraveyardIntoLibrary(String),
+	/// exiles a player's whole graveyard.
+	ExileGraveyard(String),
+	/// every player rolls a d20 to see who goes first (the ChooseFirstTurn step).
+	RollForFirstTurn,
+	/// everyone shuffles and draws their opening hand.
+	DrawOpeningHands,
+	/// a player mulligans. Ignored if it isn't their turn to decide.
+	Mulligan(String),
+	/// a player keeps their hand, putting `bottom` on the bottom of their library.
+	KeepHand{player_name: String, bottom: Vec<CardId>},
+	/// a commander dealt combat damage to a player.
+	DealCommanderDamage{player_name: String, commander: CardId, amount: u32},
 }
 
+/// runs an action, then processes every event it queued up, in order. What happened ends up in
+/// state.events.last_processed, and any abilities that triggered end up in state.pending_triggers.
 pub fn reducer(mut state: GameState, action: Action) -> GameState {
+	state.action_log.push(action.clone());
+	state.rejected_action = None;
+	let mut state = apply(state, action);
+	let mut processed = vec![];
+	// giving someone priority checks state-based actions, which can queue more events (and those
+	// can trigger more abilities), so keep going until nothing new gets queued.
+	loop {
+		state.update_trigger_subscriptions();
+		let batch = state.events.process_all();
+		state.collect_triggers(&batch);
+		state.handle_new_triggers();
+		processed.extend(batch);
+		if state.events.pending().next().is_none() {
+			break;
+		}
+	}
+	state.events.last_processed = processed;
+	state
+}
+
+fn apply(mut state: GameState, action: Action) -> GameState {
 	match action{
-		Action::UpdateCurrentEvent {event} => GameState {
-			current_event: event,
-			..state
+		Action::UpdateCurrentEvent {event} => {
+			state.queue_events(vec![event]);
+			state
 		},
 		Action::ProgressMetaGamePhase => GameState{
 			 meta_game_phase: {
@@ -80,7 +186,9 @@ pub fn reducer(mut state: GameState, action: Action) -> GameState {
 					 ConfirmLibrary => ChooseFirstTurn,
 					 ChooseFirstTurn => InitialDraw,
 					 InitialDraw => MulliganChoice,
-					 MulliganChoice => ActiveGame,
+					 // the game can't start until everybody's kept their hand.
+					 MulliganChoice if state.all_hands_kept() => ActiveGame,
+					 MulliganChoice => MulliganChoice,
 					 ActiveGame => EndGame,
 					 EndGame => DeclareWinner,
 					 DeclareWinner => ChoosePlayerCount,
@@ -112,33 +220,400 @@ pub fn reducer(mut state: GameState, action: Action) -> GameState {
 			},
 			..state
 		},
-		Action::ProgressTurnPhase => GameState {
-			turn_phase: {
-				use crate::state_manager::TurnPhase::*;
-				match state.turn_phase {
-					Untap => Upkeep,
-					Upkeep => Draw,
-					Draw => PreCombatMainPhase,
-					PreCombatMainPhase => CombatPhase,
-					CombatPhase => DeclareAttackers,
-					DeclareAttackers => DeclareBlockers,
-					DeclareBlockers => CombatResolution,
-					CombatResolution => EndCombat,
-					EndCombat => PostCombatMainPhase,
-					PostCombatMainPhase => End,
-					End => Cleanup,
-					Cleanup => PassTurn,
-					PassTurn => Untap,
-				}
-			},
-			..state
+		Action::ProgressTurnPhase => {
+			// a step only ends once everybody's passed with nothing on the stack.
+			if state.step_can_end() {
+				state.begin_next_step();
+			}
+			state
 		},
-		Action::AddPlayer(name) => GameState{
-			player_turn: {
-				state.player_turn.add_player(name)
-			},
+		Action::AddPlayer(name) => {
+			let starting_life = state.rules.starting_life;
+			state.player_turn.add_player(name.clone());
+			if let Some(player) = state.player_turn.get_player_mut(&name) {
+				player.life = starting_life;
+			}
+			state
+		},
+		Action::AddDeck(deck) => {
+			state.add_deck(*deck);
+			state
+		},
+		Action::CreateToken {player_name, definition} => {
+			if let Some(card_id) = state.create_token(&player_name, &definition) {
+				let event = CurrentEvent::CardPlacedOnBattlefield(EventSource::Card(player_name, card_id));
+				state.queue_events(vec![event]);
+			}
+			state
+		},
+		Action::PutCounters {target, counter, amount} => {
+			match &target {
+				EventSource::Player(player) => match state.player_turn.get_player_mut(&player.name) {
+					Some(player) => player.counters.add(counter, amount),
+					None => return state
+				},
+				EventSource::Card(player_name, card_id) => match state.get_deck_mut(player_name).and_then(|deck| deck.get_card(*card_id)) {
+					Some(card) => card.add_counters(counter, amount),
+					None => return state
+				},
+				_ => return state
+			}
+			state.queue_events(vec![CurrentEvent::CounterPlaced(target)]);
+			state
+		},
+		Action::RemoveCounters {target, counter, amount} => {
+			match &target {
+				EventSource::Player(player) => match state.player_turn.get_player_mut(&player.name) {
+					Some(player) => { player.counters.remove(&counter, amount); },
+					None => return state
+				},
+				EventSource::Card(player_name, card_id) => match state.get_deck_mut(player_name).and_then(|deck| deck.get_card(*card_id)) {
+					Some(card) => { card.remove_counters(&counter, amount); },
+					None => return state
+				},
+				_ => return state
+			}
+			state.queue_events(vec![CurrentEvent::CounterRemoved(target)]);
+			state
+		},
+		Action::SetGameRules(rules) => GameState{
+			rules,
 			..state
 		},
+		Action::SwapSideboardCards {player_name, take_out, bring_in} => {
+			let validator = state.rules.validator();
+			let swapped = match state.get_deck_mut(&player_name) {
+				Some(deck) => deck.swap_sideboard_cards(&take_out, &bring_in, validator.as_ref()),
+				None => return state
+			};
+			if let Err(error) = swapped {
+				state.rejected_action = Some(ActionError::Sideboard(error));
+			}
+			state
+		},
+		Action::RestoreRegisteredDeck(player_name) => {
+			if let Some(deck) = state.get_deck_mut(&player_name) {
+				deck.restore_registered_list();
+			}
+			state
+		},
+		Action::ShuffleLibrary(player_name) => {
+			// not get_deck_mut, so the deck and the rng can be borrowed at the same time.
+			let deck = match state.decks.iter_mut().find(|deck| deck.player.name == player_name) {
+				Some(deck) => deck,
+				None => return state
+			};
+			deck.shuffle_library(&mut state.rng);
+			let event = CurrentEvent::LibraryShuffled(EventSource::Player(deck.player.clone()));
+			state.queue_events(vec![event]);
+			state
+		},
+		Action::DrawCard(player_name) => {
+			let events = state.draw_card(&player_name);
+			state.queue_events(events);
+			state
+		},
+		Action::CheckStateBasedActions => {
+			let events = state.check_state_based_actions();
+			state.queue_events(events);
+			state
+		},
+		Action::MoveCard {player_name, card_id, from, to} => {
+			let events = match state.get_deck_mut(&player_name) {
+				Some(deck) => deck.move_card_with_events(card_id, from, to).unwrap_or_default(),
+				None => return state
+			};
+			state.queue_events(events);
+			state
+		},
+		Action::CastSpell {player_name, card_id, choices} => {
+			match state.cast_spell(&player_name, card_id, choices) {
+				Ok(events) => state.queue_events(events),
+				Err(error) => state.rejected_action = Some(ActionError::Cast(error)),
+			}
+			state
+		},
+		Action::PassPriority(player_name) => {
+			match state.pass_priority(&player_name) {
+				Ok(events) => state.queue_events(events),
+				Err(error) => state.rejected_action = Some(ActionError::Priority(error)),
+			}
+			state
+		},
+		Action::OrderTriggers {player_name, order} => {
+			if let Err(error) = state.order_triggers(&player_name, &order) {
+				state.rejected_action = Some(ActionError::Trigger(error));
+			}
+			state
+		},
+		Action::ShuffleGraveyardIntoLibrary(player_name) => {
+			let deck = match state.decks.iter_mut().find(|deck| deck.player.name == player_name) {
+				Some(deck) => deck,
+				None => return state
+			};
+			let events = deck.shuffle_graveyard_into_library(&mut state.rng);
+			state.queue_events(events);
+			state
+		},
+		Action::ExileGraveyard(player_name) => {
+			let events = match state.get_deck_mut(&player_name) {
+				Some(deck) => deck.exile_graveyard(),
+				None => return state
+			};
+			state.queue_events(events);
+			state
+		},
+		Action::RollForFirstTurn => {
+			state.roll_for_first_turn();
+			state
+		},
+		Action::DrawOpeningHands => {
+			state.draw_opening_hands();
+			state
+		},
+		Action::Mulligan(player_name) => {
+			if state.mulligan(&player_name).is_err() {
+				return state;
+			}
+			let event = CurrentEvent::PlayerMulliganed(EventSource::Player(state.player(&player_name)));
+			state.queue_events(vec![event]);
+			state
+		},
+		Action::KeepHand {player_name, bottom} => {
+			if state.keep_hand(&player_name, &bottom).is_err() {
+				return state;
+			}
+			let event = CurrentEvent::PlayerKeptHand(EventSource::Player(state.player(&player_name)));
+			state.queue_events(vec![event]);
+			state
+		},
+		Action::DealCommanderDamage {player_name, commander, amount} => {
+			let player = match state.player_turn.get_player_mut(&player_name) {
+				Some(player) => player,
+				None => return state
+			};
+			// losing to 21 commander damage is a state-based action, so it waits for the next check.
+			player.take_commander_damage(commander, amount);
+			let event = CurrentEvent::PlayerDealtDamage(EventSource::Player(player.clone()));
+			state.queue_events(vec![event]);
+			state
+		},
 	}
 }
 
+#[cfg(test)]
+mod tests {
+	use redux_rs::Selector;
+	use crate::selectors::SelectRejectedAction;
+	use super::*;
+
+	#[tokio::test]
+	async fn decks_live_in_the_store(){
+		use redux_rs::Store;
+		use crate::selectors::SelectDeck;
+		let store = Store::new_with_state(reducer, GameState::new());
+		let deck = Deck{ player: Player::new("Andrew"), ..Default::default() };
+		store.dispatch(Action::AddDeck(Box::new(deck))).await;
+		assert!(store.select(SelectDeck("Andrew".to_string())).await.is_some());
+		assert!(store.select(SelectDeck("Rory".to_string())).await.is_none());
+	}
+
+	#[test]
+	fn put_counters_gives_a_player_poison(){
+		let state = reducer(GameState::new(), Action::AddPlayer("Andrew".to_string()));
+		let target = EventSource::Player(Player::new("Andrew"));
+		let state = reducer(state, Action::PutCounters {
+			target: target.clone(),
+			counter: CounterType::Poison,
+			amount: 3
+		});
+		assert_eq!(state.player_turn.players[0].counters.get(&CounterType::Poison), 3);
+		assert_eq!(state.current_event(), CurrentEvent::CounterPlaced(target.clone()));
+
+		let state = reducer(state, Action::RemoveCounters {
+			target,
+			counter: CounterType::Poison,
+			amount: 1
+		});
+		assert_eq!(state.player_turn.players[0].counters.get(&CounterType::Poison), 2);
+	}
+
+	#[test]
+	fn put_counters_grows_a_card(){
+		let cards = vec![CardListItem("Grizzly Bears".to_string(), 1)];
+		let deck = Deck::new(Player::new("Andrew"), &cards, &vec![]).unwrap();
+		let state = reducer(GameState::new(), Action::AddDeck(Box::new(deck)));
+		let bears = state.get_deck("Andrew").unwrap().library[0];
+		let target = EventSource::Card("Andrew".to_string(), bears);
+		let state = reducer(state, Action::PutCounters {
+			target: target.clone(),
+			counter: CounterType::PlusOnePlusOne,
+			amount: 2
+		});
+		let card = state.get_deck("Andrew").unwrap().get_card_immut(bears).unwrap();
+		assert_eq!(card.counters.get(&CounterType::PlusOnePlusOne), 2);
+		assert_eq!(card.battlefield_behavior.power(), 4);
+		assert_eq!(state.current_event(), CurrentEvent::CounterPlaced(target.clone()));
+
+		let state = reducer(state, Action::RemoveCounters {
+			target,
+			counter: CounterType::PlusOnePlusOne,
+			amount: 1
+		});
+		let card = state.get_deck("Andrew").unwrap().get_card_immut(bears).unwrap();
+		assert_eq!(card.counters.get(&CounterType::PlusOnePlusOne), 1);
+
+		// no event for a card that isn't there.
+		let state = reducer(state, Action::PutCounters {
+			target: EventSource::Card("Rory".to_string(), bears),
+			counter: CounterType::PlusOnePlusOne,
+			amount: 1
+		});
+		assert!(state.events.last_processed.is_empty());
+	}
+
+	#[test]
+	fn sideboarding_between_games_respects_the_format(){
+		let cards = vec![CardListItem("Lightning Bolt".to_string(), 4), CardListItem("Mountain".to_string(), 56)];
+		let sideboard = vec![CardListItem("Soldier of Fortune".to_string(), 4)];
+		let deck = Deck::new(Player::new("Andrew"), &cards, &sideboard).unwrap();
+		let rules = GameRules{ format: Some(crate::deck_validation::Format::Modern), ..Default::default() };
+		let state = reducer(GameState::new(), Action::SetGameRules(rules));
+		let state = reducer(state, Action::AddDeck(Box::new(deck)));
+
+		let state = reducer(state, Action::SwapSideboardCards {
+			player_name: "Andrew".to_string(),
+			take_out: vec![CardListItem("Lightning Bolt".to_string(), 4)],
+			bring_in: vec![],
+		});
+		assert_eq!(state.get_deck("Andrew").unwrap().library.len(), 60);
+		assert!(matches!(
+			SelectRejectedAction.select(&state),
+			Some(ActionError::Sideboard(SideboardError::IllegalDeck(_)))
+		));
+
+		let state = reducer(state, Action::SwapSideboardCards {
+			player_name: "Andrew".to_string(),
+			take_out: vec![CardListItem("Lightning Bolt".to_string(), 4)],
+			bring_in: vec![CardListItem("Soldier of Fortune".to_string(), 4)],
+		});
+		assert_eq!(state.get_deck("Andrew").unwrap().search_cards("Lightning Bolt".to_string()).len(), 0);
+		assert_eq!(SelectRejectedAction.select(&state), None);
+
+		let state = reducer(state, Action::RestoreRegisteredDeck("Andrew".to_string()));
+		assert_eq!(state.get_deck("Andrew").unwrap().search_cards("Lightning Bolt".to_string()).len(), 4);
+	}
+
+	#[test]
+	fn rejected_casts_and_passes_say_why(){
+		let cards = vec![CardListItem("Lightning Bolt".to_string(), 1)];
+		let deck = Deck::new(Player::new("Andrew"), &cards, &vec![]).unwrap();
+		let state = reducer(GameState::new(), Action::AddDeck(Box::new(deck)));
+		let bolt = state.get_deck("Andrew").unwrap().library[0];
+		let state = reducer(state, Action::CastSpell {
+			player_name: "Andrew".to_string(),
+			card_id: bolt,
+			choices: SpellChoices::default(),
+		});
+		assert_eq!(SelectRejectedAction.select(&state), Some(ActionError::Cast(CastError::NoPriority)));
+		let state = reducer(state, Action::PassPriority("Andrew".to_string()));
+		assert_eq!(SelectRejectedAction.select(&state), Some(ActionError::Priority(PriorityError::NotYourPriority)));
+		let state = reducer(state, Action::ShuffleLibrary("Andrew".to_string()));
+		assert_eq!(SelectRejectedAction.select(&state), None);
+	}
+
+	#[test]
+	fn graveyards_can_be_exiled_or_shuffled_away(){
+		let cards = vec![CardListItem("Mountain".to_string(), 10)];
+		let deck = Deck::new(Player::new("Andrew"), &cards, &vec![]).unwrap();
+		let mut state = reducer(GameState::with_seed(4), Action::AddDeck(Box::new(deck)));
+		let deck = state.get_deck_mut("Andrew").unwrap();
+		deck.mill_card();
+		deck.mill_card();
+		let state = reducer(state, Action::ShuffleGraveyardIntoLibrary("Andrew".to_string()));
+		assert_eq!(state.get_deck("Andrew").unwrap().library.len(), 10);
+		assert!(matches!(state.events.last_processed[0].event, CurrentEvent::GraveyardShuffledIntoLibrary(_)));
+		assert!(matches!(state.current_event(), CurrentEvent::LibraryShuffled(_)));
+
+		let mut state = state;
+		state.get_deck_mut("Andrew").unwrap().mill_card();
+		let state = reducer(state, Action::ExileGraveyard("Andrew".to_string()));
+		assert_eq!(state.get_deck("Andrew").unwrap().exile.len(), 1);
+		assert!(matches!(state.events.last_processed[0].event, CurrentEvent::GraveyardExiled(_)));
+		assert!(matches!(state.current_event(), CurrentEvent::CardPlacedInExile(_)));
+	}
+
+	#[test]
+	fn mulligan_events_have_the_real_player_in_them(){
+		let rules = GameRules{ starting_life: 40, ..Default::default() };
+		let mut state = reducer(GameState::with_seed(5), Action::SetGameRules(rules));
+		for name in ["Andrew", "Rory"] {
+			state = reducer(state, Action::AddPlayer(name.to_string()));
+			let deck = Deck::new(Player::new(name), &vec![CardListItem("Forest".to_string(), 20)], &vec![]).unwrap();
+			state = reducer(state, Action::AddDeck(Box::new(deck)));
+		}
+		let state = reducer(state, Action::DrawOpeningHands);
+		let state = reducer(state, Action::Mulligan("Andrew".to_string()));
+		let andrew = state.player_turn.get_player("Andrew").unwrap().clone();
+		assert_eq!(andrew.life, 40);
+		assert_eq!(state.current_event(), CurrentEvent::PlayerMulliganed(EventSource::Player(andrew.clone())));
+		let state = reducer(state, Action::KeepHand{ player_name: "Rory".to_string(), bottom: vec![] });
+		let rory = state.player_turn.get_player("Rory").unwrap().clone();
+		assert_eq!(state.current_event(), CurrentEvent::PlayerKeptHand(EventSource::Player(rory)));
+	}
+
+	#[tokio::test]
+	async fn one_action_can_cause_a_bunch_of_events(){
+		use redux_rs::Store;
+		use crate::selectors::{SelectDeck, SelectEventLog, SelectLastEvents};
+		let cards = vec![CardListItem("Mountain".to_string(), 3)];
+		let mut deck = Deck::new(Player::new("Andrew"), &cards, &vec![]).unwrap();
+		deck.mill_card();
+		deck.mill_card();
+		let store = Store::new_with_state(reducer, GameState::with_seed(3));
+		store.dispatch(Action::AddDeck(Box::new(deck))).await;
+		// the game gave the cards new ids.
+		let milled = store.select(SelectDeck("Andrew".to_string())).await.unwrap().graveyard;
+		store.dispatch(Action::ExileGraveyard("Andrew".to_string())).await;
+
+		let events = store.select(SelectLastEvents).await;
+		assert_eq!(events.len(), 3);
+		assert!(matches!(events[0], CurrentEvent::GraveyardExiled(_)));
+		assert_eq!(events[1], CurrentEvent::CardPlacedInExile(EventSource::Card("Andrew".to_string(), milled[0])));
+		assert_eq!(events[2], CurrentEvent::CardPlacedInExile(EventSource::Card("Andrew".to_string(), milled[1])));
+
+		store.dispatch(Action::DrawCard("Andrew".to_string())).await;
+		assert_eq!(store.select(SelectLastEvents).await.len(), 1);
+		assert_eq!(store.select(SelectEventLog).await.len(), 4);
+	}
+
+	#[test]
+	fn subscribed_cards_hear_about_the_events_they_care_about(){
+		let mut state = GameState::with_seed(3);
+		let card_id = CardId::generate();
+		state.events.subscribe("Andrew", card_id, vec![EventKind::PlayerKilled]);
+		let state = reducer(state, Action::UpdateCurrentEvent { event: CurrentEvent::LibraryShuffled(EventSource::NaturalProgression) });
+		assert!(state.events.last_processed[0].subscribers.is_empty());
+		let state = reducer(state, Action::UpdateCurrentEvent { event: CurrentEvent::PlayerKilled(EventSource::NaturalProgression) });
+		assert_eq!(state.events.last_processed[0].subscribers[0].card_id, card_id);
+	}
+
+	#[test]
+	fn commander_games_start_at_forty_life(){
+		let state = reducer(GameState::new(), Action::SetGameRules(GameRules::commander()));
+		let state = reducer(state, Action::AddPlayer("Andrew".to_string()));
+		assert_eq!(state.player_turn.players[0].life, 40);
+		let commander = CardId::generate();
+		let state = reducer(state, Action::DealCommanderDamage {
+			player_name: "Andrew".to_string(),
+			commander,
+			amount: 21
+		});
+		assert_eq!(state.player_turn.players[0].life, 19);
+		assert!(matches!(state.current_event(), CurrentEvent::PlayerDealtDamage(_)));
+		assert!(!state.player_turn.players[0].has_left_game);
+		let state = reducer(state, Action::CheckStateBasedActions);
+		assert!(matches!(state.current_event(), CurrentEvent::PlayerKilled(_)));
+		assert!(state.player_turn.players[0].has_left_game);
+	}
+}
@@ -0,0 +1,90 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng, SeedableRng};
+
+/// The one source of randomness for a game. Every shuffle, coin flip and die roll goes through
+/// this, so a game played from the same seed with the same actions comes out exactly the same.
+/// (StdRng's output can change between versions of rand, so replays only hold up as long as
+/// Cargo.lock doesn't change the rand version.)
+#[derive(Debug, Clone)]
+pub struct GameRng {
+	seed: u64,
+	rng: StdRng,
+}
+
+impl GameRng {
+	pub fn new(seed: u64) -> GameRng {
+		GameRng {
+			seed,
+			rng: StdRng::seed_from_u64(seed),
+		}
+	}
+
+	/// picks a random seed (and remembers it so the game can still be replayed).
+	pub fn from_entropy() -> GameRng {
+		GameRng::new(thread_rng().gen())
+	}
+
+	/// the seed the game started with.
+	pub fn seed(&self) -> u64 {
+		self.seed
+	}
+
+	pub fn shuffle<T>(&mut self, items: &mut [T]) {
+		items.shuffle(&mut self.rng);
+	}
+
+	/// true is heads.
+	pub fn flip_coin(&mut self) -> bool {
+		self.rng.gen_bool(0.5)
+	}
+
+	/// rolls a die with `sides` sides; returns 1 through `sides`.
+	pub fn roll_die(&mut self, sides: u32) -> u32 {
+		self.rng.gen_range(1..=sides)
+	}
+
+	/// picks a random index into something `len` long, like for discarding at random. None if
+	/// there's nothing to pick.
+	pub fn choose_index(&mut self, len: usize) -> Option<usize> {
+		if len == 0 {
+			return None;
+		}
+		Some(self.rng.gen_range(0..len))
+	}
+}
+
+impl Default for GameRng {
+	fn default() -> Self {
+		GameRng::new(0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn the_same_seed_gives_the_same_game(){
+		let mut first = GameRng::new(1993);
+		let mut second = GameRng::new(1993);
+		let mut first_cards: Vec<u32> = (0..60).collect();
+		let mut second_cards: Vec<u32> = (0..60).collect();
+		first.shuffle(&mut first_cards);
+		second.shuffle(&mut second_cards);
+		assert_eq!(first_cards, second_cards);
+		assert_eq!(first.roll_die(20), second.roll_die(20));
+		assert_eq!(first.flip_coin(), second.flip_coin());
+		assert_eq!(first.seed(), 1993);
+	}
+
+	#[test]
+	fn dice_stay_in_range(){
+		let mut rng = GameRng::new(7);
+		for _ in 0..200 {
+			let roll = rng.roll_die(20);
+			assert!((1..=20).contains(&roll));
+		}
+		assert_eq!(rng.choose_index(0), None);
+	}
+}
@@ -1,6 +1,9 @@
 use redux_rs::Selector;
-use crate::reducers::CurrentEvent;
+use crate::deck::Deck;
+use crate::reducers::{ActionError, CurrentEvent};
 use crate::state_manager::{GameState, MetaGamePhase, Player, PlayerTurn, TurnPhase};
+use crate::stack::StackObject;
+use crate::triggers::PendingTrigger;
 
 pub struct SelectPlayerCount;
 
@@ -29,7 +32,7 @@ impl Selector<GameState> for SelectFirstPlayer{
 		if state.player_turn.players.len() > 0 {
 			return state.player_turn.players[0].clone();
 		}
-		Player{name: "No Players Added Yet".to_string() }
+		Player::new("No Players Added Yet")
 	}
 }
 
@@ -43,7 +46,27 @@ pub struct SelectCurrentEvent;
 impl Selector<GameState> for SelectCurrentEvent {
 	type Result = CurrentEvent;
 	fn select(&self, state: &GameState) -> Self::Result {
-		state.current_event.clone()
+		state.current_event()
+	}
+}
+
+/// the events the last action caused, in the order they happened.
+pub struct SelectLastEvents;
+
+impl Selector<GameState> for SelectLastEvents {
+	type Result = Vec<CurrentEvent>;
+	fn select(&self, state: &GameState) -> Self::Result {
+		state.events.last_processed.iter().map(|processed| processed.event.clone()).collect()
+	}
+}
+
+/// every event that's happened this game.
+pub struct SelectEventLog;
+
+impl Selector<GameState> for SelectEventLog {
+	type Result = Vec<CurrentEvent>;
+	fn select(&self, state: &GameState) -> Self::Result {
+		state.events.log.clone()
 	}
 }
 
@@ -73,3 +96,73 @@ impl Selector<GameState> for SelectTurnPhase {
 		state.turn_phase.clone()
 	}
 }
+
+/// selects a player's deck by player name.
+pub struct SelectDeck(pub String);
+
+impl Selector<GameState> for SelectDeck {
+	type Result = Option<Deck>;
+	fn select(&self, state: &GameState) -> Self::Result {
+		state.get_deck(&self.0).cloned()
+	}
+}
+
+/// a player's triggers that are waiting to go on the stack, so they can pick the order.
+pub struct SelectPendingTriggers(pub String);
+
+impl Selector<GameState> for SelectPendingTriggers {
+	type Result = Vec<PendingTrigger>;
+	fn select(&self, state: &GameState) -> Self::Result {
+		state.pending_triggers.iter().filter(|trigger| trigger.controller == self.0).cloned().collect()
+	}
+}
+
+/// whose turn it is.
+pub struct SelectActivePlayer;
+
+impl Selector<GameState> for SelectActivePlayer {
+	type Result = String;
+	fn select(&self, state: &GameState) -> Self::Result {
+		state.active_player().to_string()
+	}
+}
+
+/// who has priority right now, if anybody.
+pub struct SelectPriority;
+
+impl Selector<GameState> for SelectPriority {
+	type Result = Option<String>;
+	fn select(&self, state: &GameState) -> Self::Result {
+		state.priority.clone()
+	}
+}
+
+/// everything on the stack, top last.
+pub struct SelectStack;
+
+impl Selector<GameState> for SelectStack {
+	type Result = Vec<StackObject>;
+	fn select(&self, state: &GameState) -> Self::Result {
+		state.stack.clone()
+	}
+}
+
+/// the players the game needs input from before it can go on. Empty means the step can end.
+pub struct SelectWaitingOn;
+
+impl Selector<GameState> for SelectWaitingOn {
+	type Result = Vec<String>;
+	fn select(&self, state: &GameState) -> Self::Result {
+		state.waiting_on()
+	}
+}
+
+/// why the last action got turned down, if it did.
+pub struct SelectRejectedAction;
+
+impl Selector<GameState> for SelectRejectedAction {
+	type Result = Option<ActionError>;
+	fn select(&self, state: &GameState) -> Self::Result {
+		state.rejected_action.clone()
+	}
+}
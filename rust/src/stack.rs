@@ -0,0 +1,284 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+use regex::Regex;
+use crate::card::{CardId, CardLocation, CardType};
+use crate::colors::Color;
+use crate::commander::cost_with_commander_tax;
+use crate::cost::Cost;
+use crate::face_down::FaceDownKind;
+use crate::triggers::PendingTrigger;
+
+/// Something a spell or ability can target.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+	Player(String),
+	/// a permanent, a spell on the stack, or a card in some other zone. Args are: (player_name, card_id)
+	Card(String, CardId),
+}
+
+/// One kind of thing a target is allowed to be.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TargetKind {
+	Player,
+	/// a permanent, of that card type if there is one.
+	Permanent(Option<CardType>),
+	/// a spell on the stack, of that card type if there is one.
+	Spell(Option<CardType>),
+	/// a card in some zone besides the battlefield or the stack, like a graveyard, of that card
+	/// type if there is one.
+	Card(CardLocation, Option<CardType>),
+}
+
+/// what one "target" in some rules text is allowed to be: any of these kinds.
+pub type TargetRestriction = Vec<TargetKind>;
+
+/// A spell that's been cast and is waiting to resolve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spell {
+	pub controller: String,
+	pub card_id: CardId,
+	/// which modes were picked, for modal spells.
+	pub modes: Vec<usize>,
+	pub targets: Vec<Target>,
+	/// what each of the targets had to be when it was cast, so it can be checked again when it
+	/// resolves.
+	pub restrictions: Vec<TargetRestriction>,
+	pub x: u8,
+	/// how it was cast face down, if it was. It resolves into a face-down 2/2.
+	pub face_down: Option<FaceDownKind>,
+}
+
+/// Something on the stack waiting to resolve.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackObject {
+	Spell(Spell),
+	/// a triggered ability.
+	Ability(PendingTrigger),
+}
+
+impl StackObject {
+	pub fn controller(&self) -> &str {
+		match self {
+			StackObject::Spell(spell) => &spell.controller,
+			StackObject::Ability(trigger) => &trigger.controller,
+		}
+	}
+}
+
+/// Everything a player decides while casting a spell, after announcing it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SpellChoices {
+	/// indexes of the modes picked, for "Choose one —" spells.
+	pub modes: Vec<usize>,
+	pub targets: Vec<Target>,
+	/// what X is, if the cost has an X in it.
+	pub x: u8,
+	/// the permanents to tap for mana to pay for it.
+	pub mana: Vec<CardId>,
+	/// cast it face down for {3} using its morph or disguise ability, as a 2/2 creature spell with
+	/// no text.
+	pub face_down: Option<FaceDownKind>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CastError {
+	/// only the player with priority can cast spells.
+	NoPriority,
+	/// it's a sorcery-speed spell and it isn't their main phase (or the stack isn't empty).
+	WrongTiming,
+	/// the card isn't in their hand (or the command zone, for a commander).
+	CantCastFromThere,
+	/// lands get played, not cast.
+	NotASpell,
+	/// the wrong number of modes, or modes the spell doesn't have.
+	WrongModes,
+	/// the wrong number of targets, or targets that aren't there or aren't the right kind of thing.
+	WrongTargets,
+	/// the mana picked can't pay for it (or some of it is tapped or isn't theirs).
+	CantPay,
+	/// there are triggers waiting for someone to put them in order.
+	TriggersWaiting,
+	/// it can't be cast face down that way (it hasn't got morph, or disguise).
+	CantCastFaceDown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PriorityError {
+	NotYourPriority,
+	/// there are triggers waiting for someone to put them in order.
+	TriggersWaiting,
+}
+
+/// The modes of a modal spell ("Choose one —" followed by bullet points), and how many of them
+/// have to be picked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Modes {
+	pub min: usize,
+	pub max: usize,
+	pub modes: Vec<String>,
+}
+
+/// finds the modes in a card's text; None if it isn't modal.
+pub fn parse_modes(description: &str) -> Option<Modes> {
+	let choose = Regex::new(r"Choose (one|two|three)( or both| or more)? —").unwrap();
+	let captures = choose.captures(description)?;
+	let min = match &captures[1] {
+		"one" => 1,
+		"two" => 2,
+		_ => 3,
+	};
+	let modes: Vec<String> = description.lines()
+		.filter_map(|line| line.strip_prefix("• "))
+		.map(|mode| mode.to_string())
+		.collect();
+	let max = match captures.get(2).map(|or| or.as_str()) {
+		Some(" or both") => 2,
+		Some(" or more") => modes.len(),
+		_ => min,
+	};
+	Some(Modes { min, max, modes })
+}
+
+/// how many targets some rules text needs: one for every time it says "target".
+pub fn targets_needed(text: &str) -> usize {
+	target_restrictions(text).len()
+}
+
+/// what each target in some rules text has to be, going by the words after "target", like
+/// "target creature", "target artifact or enchantment spell", "target creature card from a
+/// graveyard" or "any target". Targets described some other way can be anything.
+pub fn target_restrictions(text: &str) -> Vec<TargetRestriction> {
+	static TARGET: OnceLock<Regex> = OnceLock::new();
+	let target = TARGET.get_or_init(|| Regex::new(r"(?i)\b(?:any target|target\b([^.,;]*))").unwrap());
+	target.captures_iter(text).map(|captures| match captures.get(1) {
+		Some(words) => restriction_from_words(&words.as_str().to_lowercase()),
+		None => vec![
+			TargetKind::Player,
+			TargetKind::Permanent(Some(CardType::Creature)),
+			TargetKind::Permanent(Some(CardType::Planeswalker)),
+			TargetKind::Permanent(Some(CardType::Battle)),
+		],
+	}).collect()
+}
+
+/// the restriction for one target from the words that come after "target", like "creature or
+/// planeswalker an opponent controls" or "instant or sorcery card from your graveyard".
+fn restriction_from_words(words: &str) -> TargetRestriction {
+	let anything = vec![TargetKind::Player, TargetKind::Permanent(None), TargetKind::Spell(None)];
+	let words: Vec<&str> = words.split_whitespace().collect();
+	// the types come first, with "or" between them.
+	let mut types_end = 1;
+	while words.get(types_end) == Some(&"or") && types_end + 1 < words.len() {
+		types_end += 2;
+	}
+	let (types, rest) = words.split_at(types_end.min(words.len()));
+	let mut types: Vec<&str> = types.iter().step_by(2).copied().collect();
+	let noun = match types.as_slice() {
+		["spell"] | ["card"] => types.pop().unwrap(),
+		_ => rest.first().copied().unwrap_or_default(),
+	};
+	let card_types: Vec<Option<CardType>> = match types.iter().filter_map(|word| CardType::from_str(word).ok()).collect::<Vec<_>>() {
+		card_types if card_types.is_empty() => vec![None],
+		card_types => card_types.into_iter().map(Some).collect(),
+	};
+	match noun {
+		"spell" => card_types.into_iter().map(TargetKind::Spell).collect(),
+		"card" | "cards" => match rest.iter().find_map(|word| zone_named(word)) {
+			Some(zone) => card_types.into_iter().map(|card_type| TargetKind::Card(zone, card_type)).collect(),
+			None => anything,
+		},
+		_ => {
+			let kinds: TargetRestriction = types.iter().filter_map(|word| match *word {
+				"player" | "opponent" => Some(TargetKind::Player),
+				"permanent" => Some(TargetKind::Permanent(None)),
+				_ => CardType::from_str(word).ok().map(|card_type| TargetKind::Permanent(Some(card_type))),
+			}).collect();
+			if kinds.is_empty() { anything } else { kinds }
+		},
+	}
+}
+
+/// the zone a word in rules text talks about, for cards targeted outside the battlefield.
+fn zone_named(word: &str) -> Option<CardLocation> {
+	match word {
+		"graveyard" | "graveyards" => Some(CardLocation::Graveyard),
+		"exile" => Some(CardLocation::Exile),
+		"hand" => Some(CardLocation::Hand),
+		"library" => Some(CardLocation::Library),
+		_ => None,
+	}
+}
+
+/// the total cost of a spell: its mana cost with X filled in, plus commander tax if it's a
+/// commander that's been cast `times_cast` times already. None if it's more generic mana than a
+/// cost can hold (nobody could pay it anyway).
+pub fn total_cost(mana_cost: &Cost, x: u8, times_cast: u32) -> Option<Cost> {
+	let mut cost = cost_with_commander_tax(mana_cost, times_cast);
+	if let Some(xs) = cost.cost.remove(&Color::X) {
+		if x > 0 {
+			cost.cost.remove(&Color::None);
+			let generic = cost.cost.entry(Color::C).or_insert(0);
+			*generic = xs.checked_mul(x).and_then(|x_mana| generic.checked_add(x_mana))?;
+		}
+	}
+	Some(cost)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::cost::parse_costs_better;
+
+	#[test]
+	fn modal_spells_know_how_many_modes_to_pick(){
+		let modes = parse_modes("Choose one —\n• Deal 3 damage to target creature.\n• Destroy target artifact.").unwrap();
+		assert_eq!((modes.min, modes.max), (1, 1));
+		assert_eq!(modes.modes[1], "Destroy target artifact.");
+		let modes = parse_modes("Choose one or both —\n• Draw a card.\n• Scry 2.").unwrap();
+		assert_eq!((modes.min, modes.max), (1, 2));
+		assert!(parse_modes("Destroy target creature.").is_none());
+		assert_eq!(targets_needed("Destroy target creature. Its controller loses 2 life."), 1);
+		assert_eq!(targets_needed("Draw two cards."), 0);
+	}
+
+	#[test]
+	fn targets_know_what_they_can_be(){
+		assert_eq!(target_restrictions("Destroy target creature."), vec![vec![TargetKind::Permanent(Some(CardType::Creature))]]);
+		assert_eq!(target_restrictions("Lightning Bolt deals 3 damage to any target.")[0].len(), 4);
+		assert!(target_restrictions("Lightning Bolt deals 3 damage to any target.")[0].contains(&TargetKind::Player));
+		assert_eq!(target_restrictions("Counter target spell."), vec![vec![TargetKind::Spell(None)]]);
+		assert_eq!(
+			target_restrictions("Counter target artifact or enchantment spell."),
+			vec![vec![TargetKind::Spell(Some(CardType::Artifact)), TargetKind::Spell(Some(CardType::Enchantment))]]
+		);
+		assert_eq!(
+			target_restrictions("Target player draws a card. Tap target creature or planeswalker."),
+			vec![
+				vec![TargetKind::Player],
+				vec![TargetKind::Permanent(Some(CardType::Creature)), TargetKind::Permanent(Some(CardType::Planeswalker))],
+			]
+		);
+		assert_eq!(
+			target_restrictions("Put target creature card from a graveyard onto the battlefield under your control."),
+			vec![vec![TargetKind::Card(CardLocation::Graveyard, Some(CardType::Creature))]]
+		);
+		assert_eq!(
+			target_restrictions("Return target instant or sorcery card from your graveyard to your hand."),
+			vec![vec![
+				TargetKind::Card(CardLocation::Graveyard, Some(CardType::Instant)),
+				TargetKind::Card(CardLocation::Graveyard, Some(CardType::Sorcery)),
+			]]
+		);
+		assert_eq!(target_restrictions("Exile target card from a graveyard."), vec![vec![TargetKind::Card(CardLocation::Graveyard, None)]]);
+	}
+
+	#[test]
+	fn x_and_commander_tax_go_into_the_total_cost(){
+		let cost = total_cost(&parse_costs_better("{X}{R}{R}"), 3, 1).unwrap();
+		assert_eq!(cost.cost.get(&Color::C), Some(&5));
+		assert_eq!(cost.cost.get(&Color::R), Some(&2));
+		assert_eq!(cost.cost.get(&Color::X), None);
+		assert_eq!(total_cost(&parse_costs_better("{X}{X}{R}"), 200, 0), None);
+		assert_eq!(total_cost(&parse_costs_better("{X}{R}"), 250, 3), None);
+	}
+}
@@ -1,5 +1,19 @@
+use std::collections::HashMap;
 use redux_rs::{Selector, Store};
-use crate::reducers::CurrentEvent;
+use crate::card::{CardId, CardIdAllocator, CardLocation, CardType};
+use crate::cost::{mana_sources, pay_cost};
+use crate::commander::COMMANDER_DAMAGE_TO_LOSE;
+use crate::counters::{Counters, CounterType};
+use crate::deck_validation::{DeckValidator, Format};
+use crate::reducers::{Action, reducer};
+use crate::rng::GameRng;
+use crate::deck::{Deck, ZoneError};
+use crate::events::{EventQueue, ProcessedEvent};
+use crate::face_down::{can_be_cast_face_down, face_down_spell_cost};
+use crate::stack::{parse_modes, target_restrictions, total_cost, CastError, PriorityError, Spell, SpellChoices, StackObject, Target, TargetKind};
+use crate::token::TokenDefinition;
+use crate::triggers::{parse_triggered_abilities, PendingTrigger, TriggerError, TriggeredAbility};
+use crate::reducers::{ActionError, CurrentEvent, EventKind, EventSource};
 use crate::state_manager::MetaGamePhase::*;
 
 #[derive(Debug, Default, Clone)]
@@ -47,16 +61,142 @@ pub enum TurnPhase{
 	PassTurn,
 }
 
+impl TurnPhase{
+	/// the only phases you can cast sorceries (and do other sorcery speed stuff) in.
+	pub fn is_main_phase(&self) -> bool{
+		*self == TurnPhase::PreCombatMainPhase || *self == TurnPhase::PostCombatMainPhase
+	}
+
+	/// the step that comes after this one.
+	pub fn next(&self) -> TurnPhase{
+		use crate::state_manager::TurnPhase::*;
+		match self {
+			Untap => Upkeep,
+			Upkeep => Draw,
+			Draw => PreCombatMainPhase,
+			PreCombatMainPhase => CombatPhase,
+			CombatPhase => DeclareAttackers,
+			DeclareAttackers => DeclareBlockers,
+			DeclareBlockers => CombatResolution,
+			CombatResolution => EndCombat,
+			EndCombat => PostCombatMainPhase,
+			PostCombatMainPhase => End,
+			End => Cleanup,
+			Cleanup => PassTurn,
+			PassTurn => Untap,
+		}
+	}
+
+	/// whether players get priority during this step. Nobody does in the untap step, and in the
+	/// cleanup step they only do if something triggers or a state-based action happens.
+	pub fn players_get_priority(&self) -> bool{
+		!matches!(self, TurnPhase::Untap | TurnPhase::Cleanup | TurnPhase::PassTurn)
+	}
+}
+
 #[derive(Debug, Default, Clone, PartialEq)]
 /// A player is someone playing the game, identified in the ChoosePlayerCount step of MetaGamePhase
 /// In the future we'll have like profile pics and avatars and stuff but right now it's just a name
 pub struct Player{
-	pub name: String
+	pub name: String,
+	pub life: i32,
+	/// poison, energy, experience and whatever other counters the player has.
+	pub counters: Counters,
+	/// how much combat damage each commander has dealt this player over the whole game.
+	pub commander_damage: HashMap<CardId, u32>,
+	/// they tried to draw a card with nothing left in their library, so they lose the next time
+	/// state-based actions get checked.
+	pub drew_from_empty_library: bool,
+	/// they've lost (or conceded) and are out of the game.
+	pub has_left_game: bool,
+}
+
+impl Player{
+	pub fn new(name: &str) -> Player{
+		Player{
+			name: name.to_string(),
+			life: 20,
+			counters: Counters::default(),
+			commander_damage: HashMap::new(),
+			drew_from_empty_library: false,
+			has_left_game: false,
+		}
+	}
+
+	/// combat damage from a commander; it costs life like any other damage, and it adds up.
+	pub fn take_commander_damage(&mut self, commander: CardId, amount: u32){
+		self.life -= amount as i32;
+		*self.commander_damage.entry(commander).or_insert(0) += amount;
+	}
+
+	/// whether the player has lost the game: no life left, 10 poison counters, 21 damage from
+	/// one commander, or they drew from an empty library.
+	pub fn has_lost(&self) -> bool{
+		self.life <= 0
+			|| self.drew_from_empty_library
+			|| self.counters.get(&CounterType::Poison) >= 10
+			|| self.commander_damage.values().any(|damage| *damage >= COMMANDER_DAMAGE_TO_LOSE)
+	}
 }
 
+#[derive(Debug, Clone, PartialEq)]
+/// Where a player is at in deciding whether to keep their opening hand.
+pub struct OpeningHand{
+	pub player_name: String,
+	/// how many times they've mulliganed.
+	pub mulligans: u32,
+	pub kept: bool,
+	/// players decide in turn order, over and over until everyone keeps; this is whether they've
+	/// decided yet this time around.
+	decided_this_round: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MulliganError{
+	/// it's someone else's turn to decide (or this player already kept).
+	NotYourDecision,
+	NoDeck,
+	/// a London mulligan puts one card on the bottom per mulligan (minus any free ones).
+	WrongNumberOfCards{expected: u32},
+	CardNotInHand,
+	/// the same card was picked to go on the bottom more than once.
+	SameCardTwice,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The rules that change between formats.
+pub struct GameRules{
+	/// the format decks have to be legal in; None for a casual game where anything goes.
+	pub format: Option<Format>,
+	pub starting_life: i32,
+	/// how many mulligans each player gets without putting a card on the bottom.
+	pub free_mulligans: u32,
+}
+
+impl Default for GameRules{
+	fn default() -> Self{
+		GameRules{
+			format: Option::None,
+			starting_life: 20,
+			free_mulligans: 0,
+		}
+	}
+}
+
+impl GameRules{
+	/// 40 life and the first mulligan is free.
+	pub fn commander() -> GameRules{
+		GameRules{
+			format: Some(Format::Commander),
+			starting_life: 40,
+			free_mulligans: 1,
+		}
+	}
 
-fn queue_events(events: Vec<CurrentEvent>){
-	todo!("implement this so you can queue a bunch of events to run one after another");
+	/// a validator for the format, if there is one.
+	pub fn validator(&self) -> Option<DeckValidator>{
+		self.format.map(DeckValidator::new)
+	}
 }
 
 
@@ -76,13 +216,23 @@ impl PlayerTurn{
 	}
 
 	pub fn add_player(&mut self, name: String) -> Self{
-		self.players.push(Player{name});
+		self.players.push(Player::new(&name));
 		Self{
 			players: self.players.clone(),
 			current_turn: self.current_turn.clone()
 		}
 	}
 
+	/// gets a player by name.
+	pub fn get_player(&self, name: &str) -> Option<&Player>{
+		self.players.iter().find(|player| player.name == name)
+	}
+
+	/// gets a mutable reference to a player by name.
+	pub fn get_player_mut(&mut self, name: &str) -> Option<&mut Player>{
+		self.players.iter_mut().find(|player| player.name == name)
+	}
+
 	pub fn change_turn(&mut self) -> Self{
 		let new_players_turn =  {
 			// find which index in the vector the player whose turn it is currently is.
@@ -108,24 +258,641 @@ impl PlayerTurn{
 
 #[derive(Debug, Default)]
 pub struct GameState{
-	pub current_event: CurrentEvent,
+	/// events waiting to be processed, the ones that already have been, and who's listening.
+	pub events: EventQueue,
 	pub meta_game_phase: MetaGamePhase,
 	pub player_turn: PlayerTurn,
 	pub turn_phase: TurnPhase,
-	// todo implement instant stack.
+	pub rules: GameRules,
+	/// every player's deck. (and all the cards in them, wherever they happen to be.)
+	pub decks: Vec<Deck>,
+	/// where every random thing in the game comes from. Its seed plus action_log is enough to
+	/// replay the whole game.
+	pub rng: GameRng,
+	/// every action dispatched so far, in order.
+	pub action_log: Vec<Action>,
+	/// where the ids of every card in the game come from, so replays give them the same ones.
+	pub card_ids: CardIdAllocator,
+	/// everyone's opening hand decisions, in turn order.
+	pub opening_hands: Vec<OpeningHand>,
+	/// spells and abilities waiting to resolve; the top of the stack is the end of the vec.
+	pub stack: Vec<StackObject>,
+	/// who can cast a spell (or pass) right now, if anyone.
+	pub priority: Option<String>,
+	/// the players who've passed priority in a row without anything happening in between.
+	pub passed_priority: Vec<String>,
+	/// players who've already picked the order of their pending triggers.
+	pub triggers_ordered: Vec<String>,
+	/// the triggered abilities of every permanent on the battlefield (an empty vec if it hasn't
+	/// got any). Kept up to date by update_trigger_subscriptions.
+	pub triggered_abilities: HashMap<CardId, Vec<TriggeredAbility>>,
+	/// abilities that have triggered and will go on the stack the next time a player would get
+	/// priority.
+	pub pending_triggers: Vec<PendingTrigger>,
+	/// why the last action didn't do anything, if it got turned down.
+	pub rejected_action: Option<ActionError>,
+	/// players got priority during this cleanup step, so there's another cleanup step after it
+	/// instead of the turn ending.
+	pub repeat_cleanup: bool,
 }
 
 
 impl GameState{
+	/// a new game with a random seed.
 	pub fn new() -> Self{
+		Self::with_seed(GameRng::from_entropy().seed())
+	}
+
+	/// a new game whose randomness all comes from `seed`.
+	pub fn with_seed(seed: u64) -> Self{
 		Self{
-			current_event: CurrentEvent::NewGame,
+			events: EventQueue::default(),
 			meta_game_phase: MetaGamePhase::ChoosePlayerCount,
 			player_turn: PlayerTurn{
 				players: vec![]
-			,	current_turn: Player{name: "default".to_string()}
+			,	current_turn: Player::new("default")
+			},
+			turn_phase: TurnPhase::Untap,
+			rules: GameRules::default(),
+			decks: vec![],
+			rng: GameRng::new(seed),
+			action_log: vec![],
+			card_ids: CardIdAllocator::default(),
+			opening_hands: vec![],
+			stack: vec![],
+			priority: Option::None,
+			passed_priority: vec![],
+			triggers_ordered: vec![],
+			triggered_abilities: HashMap::new(),
+			pending_triggers: vec![],
+			rejected_action: Option::None,
+			repeat_cleanup: false,
+		}
+	}
+
+	/// the last event that got processed; NewGame if nothing's happened yet.
+	pub fn current_event(&self) -> CurrentEvent{
+		self.events.latest().cloned().unwrap_or(CurrentEvent::NewGame)
+	}
+
+	/// queues a bunch of events to run one after another. They get processed once the action
+	/// that caused them is done.
+	pub fn queue_events(&mut self, events: Vec<CurrentEvent>){
+		self.events.queue_all(events);
+	}
+
+	/// plays a game back from its seed and action log.
+	pub fn replay(seed: u64, actions: &[Action]) -> Self{
+		actions.iter().cloned().fold(GameState::with_seed(seed), reducer)
+	}
+
+	/// every player rolls a d20 (rerolling ties) and whoever rolls highest goes first.
+	/// returns that player's name.
+	pub fn roll_for_first_turn(&mut self) -> Option<String>{
+		let mut rolling: Vec<String> = self.player_turn.players.iter().map(|player| player.name.clone()).collect();
+		while rolling.len() > 1 {
+			let rolls: Vec<u32> = rolling.iter().map(|_| self.rng.roll_die(20)).collect();
+			let highest = *rolls.iter().max().unwrap();
+			rolling = rolling.into_iter().zip(rolls).filter(|(_, roll)| *roll == highest).map(|(name, _)| name).collect();
+		}
+		let winner = rolling.pop()?;
+		self.player_turn.current_turn = self.player_turn.players.iter().find(|player| player.name == winner)?.clone();
+		Some(winner)
+	}
+
+	/// every player's name in turn order, starting with whoever's turn it is. (This is also APNAP
+	/// order.)
+	pub fn turn_order(&self) -> Vec<String>{
+		let players = &self.player_turn.players;
+		let first = players.iter().position(|player| player.name == self.player_turn.current_turn.name).unwrap_or(0);
+		players.iter().cycle().skip(first).take(players.len())
+			.map(|player| player.name.clone())
+			.collect()
+	}
+
+	/// adds a player's deck, replacing the one they've got if there is one. Its cards get new ids
+	/// from the game.
+	pub fn add_deck(&mut self, mut deck: Deck){
+		deck.renumber_cards(&mut self.card_ids);
+		self.decks.retain(|d| d.player.name != deck.player.name);
+		self.decks.push(deck);
+	}
+
+	/// creates a token on the battlefield for a player. Returns its id, or None if the player
+	/// hasn't got a deck.
+	pub fn create_token(&mut self, player_name: &str, definition: &TokenDefinition) -> Option<CardId>{
+		let id = self.card_ids.next_id();
+		self.get_deck_mut(player_name)?.create_token(definition, id);
+		Some(id)
+	}
+
+	/// a copy of a player, for putting in events. Falls back to a new player with that name if
+	/// they haven't been added to the game.
+	pub fn player(&self, player_name: &str) -> Player{
+		self.player_turn.get_player(player_name).cloned().unwrap_or_else(|| Player::new(player_name))
+	}
+
+	/// gets a player's deck by the player's name.
+	pub fn get_deck(&self, player_name: &str) -> Option<&Deck>{
+		self.decks.iter().find(|deck| deck.player.name == player_name)
+	}
+
+	/// gets a mutable reference to a player's deck by the player's name.
+	pub fn get_deck_mut(&mut self, player_name: &str) -> Option<&mut Deck>{
+		self.decks.iter_mut().find(|deck| deck.player.name == player_name)
+	}
+
+	/// every player with a deck shuffles and draws 7 (the InitialDraw step). Mulligan decisions
+	/// then go in turn order starting with whoever goes first.
+	pub fn draw_opening_hands(&mut self){
+		self.opening_hands = vec![];
+		for name in self.turn_order(){
+			if let Some(deck) = self.decks.iter_mut().find(|deck| deck.player.name == name){
+				deck.draw_opening_hand(&mut self.rng);
+				self.opening_hands.push(OpeningHand{
+					player_name: name,
+					mulligans: 0,
+					kept: false,
+					decided_this_round: false,
+				});
+			}
+		}
+	}
+
+	/// whose turn it is to keep or mulligan, if anyone's.
+	pub fn next_mulligan_decision(&self) -> Option<&str>{
+		self.opening_hands.iter()
+			.find(|hand| !hand.kept && !hand.decided_this_round)
+			.map(|hand| hand.player_name.as_str())
+	}
+
+	/// how many cards a player has to put on the bottom when they keep. Free mulligans (like the
+	/// first one in Commander) don't count.
+	pub fn cards_to_bottom(&self, player_name: &str) -> u32{
+		self.opening_hands.iter()
+			.find(|hand| hand.player_name == player_name)
+			.map(|hand| hand.mulligans.saturating_sub(self.rules.free_mulligans))
+			.unwrap_or(0)
+	}
+
+	/// the player shuffles their hand back in and draws a new 7.
+	pub fn mulligan(&mut self, player_name: &str) -> Result<(), MulliganError>{
+		self.check_mulligan_decision(player_name)?;
+		let deck = self.decks.iter_mut().find(|deck| deck.player.name == player_name).ok_or(MulliganError::NoDeck)?;
+		deck.mulligan(&mut self.rng);
+		let hand = self.opening_hands.iter_mut().find(|hand| hand.player_name == player_name).unwrap();
+		hand.mulligans += 1;
+		hand.decided_this_round = true;
+		self.start_next_mulligan_round();
+		Ok(())
+	}
+
+	/// the player keeps their hand and puts `bottom` on the bottom of their library (in order from
+	/// top to bottom). Technically that happens once everyone has kept, but nobody can see the
+	/// cards anyway so it's the same thing.
+	pub fn keep_hand(&mut self, player_name: &str, bottom: &[CardId]) -> Result<(), MulliganError>{
+		self.check_mulligan_decision(player_name)?;
+		let expected = self.cards_to_bottom(player_name);
+		if bottom.len() as u32 != expected{
+			return Err(MulliganError::WrongNumberOfCards{expected});
+		}
+		let deck = self.get_deck_mut(player_name).ok_or(MulliganError::NoDeck)?;
+		if !bottom.iter().all(|card_id| deck.hand.contains(card_id)){
+			return Err(MulliganError::CardNotInHand);
+		}
+		deck.put_on_bottom(bottom).map_err(|error| match error {
+			ZoneError::SameCardTwice => MulliganError::SameCardTwice,
+			_ => MulliganError::CardNotInHand,
+		})?;
+		let hand = self.opening_hands.iter_mut().find(|hand| hand.player_name == player_name).unwrap();
+		hand.kept = true;
+		hand.decided_this_round = true;
+		self.start_next_mulligan_round();
+		Ok(())
+	}
+
+	/// whether everybody's kept their opening hand, so the game can start.
+	pub fn all_hands_kept(&self) -> bool{
+		!self.opening_hands.is_empty() && self.opening_hands.iter().all(|hand| hand.kept)
+	}
+
+	fn check_mulligan_decision(&self, player_name: &str) -> Result<(), MulliganError>{
+		match self.next_mulligan_decision(){
+			Some(next) if next == player_name => Ok(()),
+			_ => Err(MulliganError::NotYourDecision),
+		}
+	}
+
+	/// once everybody still deciding has decided, the ones that mulliganed go again.
+	fn start_next_mulligan_round(&mut self){
+		if self.next_mulligan_decision().is_none(){
+			for hand in self.opening_hands.iter_mut(){
+				hand.decided_this_round = false;
+			}
+		}
+	}
+
+	/// the player draws a card. Drawing from an empty library doesn't do anything right away; they
+	/// lose the next time state-based actions are checked, unless they control something like
+	/// Laboratory Maniac that says they win instead.
+	pub fn draw_card(&mut self, player_name: &str) -> Vec<CurrentEvent>{
+		let deck = match self.get_deck_mut(player_name){
+			Some(deck) => deck,
+			Option::None => return vec![]
+		};
+		if let (Some(_), events) = deck.draw_card(){
+			return events;
+		}
+		if let Some(card_id) = deck.wins_instead_of_drawing_from_empty_library(){
+			return self.win_game(player_name, EventSource::Card(player_name.to_string(), card_id));
+		}
+		if let Some(player) = self.player_turn.get_player_mut(player_name){
+			player.drew_from_empty_library = true;
+		}
+		vec![]
+	}
+
+	/// a player wins the game (because of `source`) and everybody else is out.
+	pub fn win_game(&mut self, player_name: &str, source: EventSource) -> Vec<CurrentEvent>{
+		for player in self.player_turn.players.iter_mut().filter(|player| player.name != player_name){
+			player.has_left_game = true;
+		}
+		vec![CurrentEvent::PlayerWon(source)]
+	}
+
+	/// checks state-based actions: players who've lost leave the game, planeswalkers with no
+	/// loyalty go to the graveyard and tokens that left the battlefield stop existing.
+	pub fn check_state_based_actions(&mut self) -> Vec<CurrentEvent>{
+		let mut events = vec![];
+		for player in self.player_turn.players.iter_mut(){
+			if !player.has_left_game && player.has_lost(){
+				player.has_left_game = true;
+				events.push(CurrentEvent::PlayerKilled(EventSource::Player(player.clone())));
+			}
+		}
+		for deck in self.decks.iter_mut(){
+			for card_id in deck.put_zero_loyalty_planeswalkers_into_graveyard(){
+				events.push(CurrentEvent::CardPlacedInGraveyard(EventSource::Card(deck.player.name.clone(), card_id)));
+			}
+			deck.remove_ceased_tokens();
+		}
+		events
+	}
+
+	/// keeps the event subscriptions in step with the battlefield: permanents with triggered
+	/// abilities listen for the events they care about, and ones that have left stop listening.
+	pub fn update_trigger_subscriptions(&mut self){
+		let on_battlefield: Vec<(String, CardId)> = self.decks.iter()
+			.flat_map(|deck| deck.battlefield.iter().map(|card_id| (deck.player.name.clone(), *card_id)))
+			.collect();
+		let gone: Vec<CardId> = self.triggered_abilities.keys()
+			.filter(|card_id| !on_battlefield.iter().any(|(_, id)| id == *card_id))
+			.cloned()
+			.collect();
+		for card_id in gone{
+			self.triggered_abilities.remove(&card_id);
+			self.events.unsubscribe_card(card_id);
+		}
+		for (player_name, card_id) in on_battlefield{
+			if self.triggered_abilities.contains_key(&card_id){
+				continue;
+			}
+			let abilities = parse_triggered_abilities(self.get_deck(&player_name).unwrap().cards[&card_id].card);
+			let mut kinds: Vec<EventKind> = vec![];
+			for ability in abilities.iter(){
+				if !kinds.contains(&ability.event){
+					kinds.push(ability.event);
+				}
+			}
+			if !kinds.is_empty(){
+				self.events.subscribe(&player_name, card_id, kinds);
+			}
+			self.triggered_abilities.insert(card_id, abilities);
+		}
+	}
+
+	/// checks every processed event against the abilities subscribed to it and adds the ones that
+	/// trigger to pending_triggers.
+	pub fn collect_triggers(&mut self, processed: &[ProcessedEvent]){
+		for processed in processed.iter(){
+			for subscription in processed.subscribers.iter(){
+				let abilities = self.triggered_abilities.get(&subscription.card_id).cloned().unwrap_or_default();
+				for ability in abilities{
+					if ability.triggers_on(&processed.event, self, &subscription.player_name, subscription.card_id){
+						self.pending_triggers.push(PendingTrigger{
+							controller: subscription.player_name.clone(),
+							source: subscription.card_id,
+							ability,
+							event: processed.event.clone(),
+						});
+					}
+				}
+			}
+		}
+	}
+
+	/// a player picks the order their pending triggers go on the stack in. `order` has the
+	/// indexes of their triggers (counting only theirs, oldest first); the first one goes on the
+	/// stack first, so it resolves last.
+	pub fn order_triggers(&mut self, player_name: &str, order: &[usize]) -> Result<(), TriggerError>{
+		let (theirs, others): (Vec<PendingTrigger>, Vec<PendingTrigger>) = self.pending_triggers.drain(..)
+			.partition(|trigger| trigger.controller == player_name);
+		let mut sorted = order.to_vec();
+		sorted.sort();
+		if sorted != (0..theirs.len()).collect::<Vec<usize>>(){
+			self.pending_triggers = others.into_iter().chain(theirs).collect();
+			return Err(TriggerError::WrongOrder);
+		}
+		self.pending_triggers = others;
+		self.pending_triggers.extend(order.iter().map(|i| theirs[*i].clone()));
+		self.triggers_ordered.push(player_name.to_string());
+		Ok(())
+	}
+
+	/// players with more than one pending trigger who haven't picked what order they go on the
+	/// stack in yet, in APNAP order.
+	pub fn waiting_to_order_triggers(&self) -> Vec<String>{
+		self.turn_order().into_iter()
+			.filter(|name| !self.triggers_ordered.contains(name))
+			.filter(|name| self.pending_triggers.iter().filter(|trigger| trigger.controller == *name).count() > 1)
+			.collect()
+	}
+
+	/// puts every pending trigger on the stack in APNAP order: the active player's go on first (in
+	/// the order they chose), then each other player's in turn order. So the last player's
+	/// triggers resolve first.
+	pub fn put_triggers_on_stack(&mut self){
+		for player_name in self.turn_order(){
+			let (theirs, others): (Vec<PendingTrigger>, Vec<PendingTrigger>) = self.pending_triggers.drain(..)
+				.partition(|trigger| trigger.controller == player_name);
+			self.pending_triggers = others;
+			self.stack.extend(theirs.into_iter().map(StackObject::Ability));
+		}
+		self.triggers_ordered.clear();
+	}
+
+	/// after an action's events have been processed: if a player has (or is about to get)
+	/// priority, whatever triggered goes on the stack, as long as everybody with more than one
+	/// trigger has put them in order. Something triggering during cleanup means players get
+	/// priority after all.
+	pub fn handle_new_triggers(&mut self){
+		if self.pending_triggers.is_empty(){
+			return;
+		}
+		if self.priority.is_none() && self.turn_phase == TurnPhase::Cleanup{
+			let active_player = self.active_player().to_string();
+			self.give_priority(&active_player);
+		}
+		if self.priority.is_some() && self.waiting_to_order_triggers().is_empty(){
+			self.put_triggers_on_stack();
+		}
+	}
+
+	/// whether a player could cast a sorcery right now: it has to be their main phase and the
+	/// stack has to be empty.
+	pub fn can_act_at_sorcery_speed(&self, player_name: &str) -> bool{
+		self.player_turn.current_turn.name == player_name && self.turn_phase.is_main_phase() && self.stack.is_empty()
+	}
+
+	/// whose turn it is.
+	pub fn active_player(&self) -> &str{
+		&self.player_turn.current_turn.name
+	}
+
+	/// gives a player priority. State-based actions get checked and anything that triggered goes
+	/// on the stack first, like the rules say (unless somebody still has to put their triggers in
+	/// order; then they go on once that's done).
+	pub fn give_priority(&mut self, player_name: &str){
+		let events = self.check_state_based_actions();
+		self.queue_events(events);
+		if self.waiting_to_order_triggers().is_empty(){
+			self.put_triggers_on_stack();
+		}
+		self.priority = Some(player_name.to_string());
+	}
+
+	/// whether the current step can end: everybody's passed in a row (or nobody gets priority
+	/// this step), the stack is empty and nothing is waiting to go on it.
+	pub fn step_can_end(&self) -> bool{
+		self.priority.is_none() && self.stack.is_empty() && self.pending_triggers.is_empty()
+	}
+
+	/// moves on to the next step and gives the active player priority if players get priority
+	/// in it. In the cleanup step they only get it if a state-based action happens.
+	pub fn begin_next_step(&mut self){
+		let step = match self.turn_phase{
+			TurnPhase::Cleanup if self.repeat_cleanup => TurnPhase::Cleanup,
+			_ => self.turn_phase.next(),
+		};
+		self.turn_phase = step.clone();
+		self.repeat_cleanup = false;
+		self.passed_priority.clear();
+		self.priority = Option::None;
+		self.queue_events(vec![CurrentEvent::BeginningOfStep(step.clone())]);
+		let active_player = self.active_player().to_string();
+		if step == TurnPhase::Untap{
+			if let Some(deck) = self.get_deck_mut(&active_player){
+				deck.untap_permanents();
+				deck.reset_loyalty_abilities();
+			}
+		}
+		if step.players_get_priority(){
+			self.give_priority(&active_player);
+		} else if step == TurnPhase::Cleanup{
+			let events = self.check_state_based_actions();
+			if !events.is_empty(){
+				self.queue_events(events);
+				self.give_priority(&active_player);
+				self.repeat_cleanup = true;
+			}
+		}
+	}
+
+	/// the players the game is waiting on right now: whoever has to put their triggers in
+	/// order, or else whoever has priority. Nobody means the step can end.
+	pub fn waiting_on(&self) -> Vec<String>{
+		let ordering = self.waiting_to_order_triggers();
+		if !ordering.is_empty(){
+			return ordering;
+		}
+		self.priority.iter().cloned().collect()
+	}
+
+	/// casts a spell from the hand (or a commander from the command zone): announce it, pick
+	/// modes and targets, work out the total cost and pay it. If any of that doesn't work out,
+	/// nothing happens. The caster gets priority back afterwards.
+	pub fn cast_spell(&mut self, player_name: &str, card_id: CardId, choices: SpellChoices) -> Result<Vec<CurrentEvent>, CastError>{
+		if self.priority.as_deref() != Some(player_name){
+			return Err(CastError::NoPriority);
+		}
+		if !self.pending_triggers.is_empty(){
+			return Err(CastError::TriggersWaiting);
+		}
+		let deck = self.get_deck(player_name).ok_or(CastError::CantCastFromThere)?;
+		let card = deck.get_card_immut(card_id).ok_or(CastError::CantCastFromThere)?;
+		let from = card.visibility_behavior.current_location;
+		let from_command_zone = from == CardLocation::Command && deck.is_commander(card_id);
+		if from != CardLocation::Hand && !from_command_zone{
+			return Err(CastError::CantCastFromThere);
+		}
+		if card.card.card_types.contains(&CardType::Land){
+			return Err(CastError::NotASpell);
+		}
+		if choices.face_down.is_some_and(|kind| !can_be_cast_face_down(&card.card.description, kind)){
+			return Err(CastError::CantCastFaceDown);
+		}
+		// face down, it's a 2/2 creature spell with no text.
+		let description = if choices.face_down.is_some() { "" } else { card.card.description.as_str() };
+		let instant_speed = choices.face_down.is_none() && card.entrance_behavior.enters_on_instant_stack();
+		if !instant_speed && !self.can_act_at_sorcery_speed(player_name){
+			return Err(CastError::WrongTiming);
+		}
+
+		let text = match parse_modes(description){
+			Some(modes) => {
+				let mut picked = choices.modes.clone();
+				picked.sort();
+				picked.dedup();
+				let count = choices.modes.len();
+				if picked.len() != count || count < modes.min || count > modes.max || picked.iter().any(|mode| *mode >= modes.modes.len()){
+					return Err(CastError::WrongModes);
+				}
+				picked.iter().map(|mode| modes.modes[*mode].clone()).collect::<Vec<String>>().join("\n")
+			},
+			Option::None if !choices.modes.is_empty() => return Err(CastError::WrongModes),
+			Option::None => description.to_string(),
+		};
+		let restrictions = target_restrictions(&text);
+		if restrictions.len() != choices.targets.len()
+			|| !choices.targets.iter().zip(&restrictions).all(|(target, kinds)| self.target_is_legal(target, kinds)){
+			return Err(CastError::WrongTargets);
+		}
+
+		let times_cast = if from_command_zone { *deck.commander_casts.get(&card_id).unwrap_or(&0) } else { 0 };
+		let mana_cost = if choices.face_down.is_some() { face_down_spell_cost() } else { card.card.mana_cost.clone() };
+		let cost = total_cost(&mana_cost, choices.x, times_cast).ok_or(CastError::CantPay)?;
+		let mut sources = vec![];
+		for (i, mana_id) in choices.mana.iter().enumerate(){
+			let permanent = deck.get_card_immut(*mana_id).ok_or(CastError::CantPay)?;
+			let usable = permanent.visibility_behavior.current_location == CardLocation::Battlefield
+				&& !permanent.battlefield_behavior.is_tapped()
+				&& !choices.mana[..i].contains(mana_id);
+			if !usable{
+				return Err(CastError::CantPay);
+			}
+			sources.extend(mana_sources(permanent.card));
+		}
+		if pay_cost(&cost, &sources).is_none(){
+			return Err(CastError::CantPay);
+		}
+
+		let deck = self.get_deck_mut(player_name).unwrap();
+		for mana_id in choices.mana.iter(){
+			deck.get_card(*mana_id).unwrap().battlefield_behavior.set_tapped(true);
+		}
+		if from_command_zone{
+			deck.cast_commander(card_id).unwrap();
+		} else {
+			deck.move_card(card_id, CardLocation::Hand, CardLocation::Stack).unwrap();
+		}
+		if let Some(kind) = choices.face_down{
+			deck.get_card(card_id).unwrap().turn_face_down(kind);
+		}
+		self.stack.push(StackObject::Spell(Spell{
+			controller: player_name.to_string(),
+			card_id,
+			modes: choices.modes,
+			targets: choices.targets,
+			restrictions,
+			x: choices.x,
+			face_down: choices.face_down,
+		}));
+		self.passed_priority.clear();
+		// like putting a card onto the battlefield face down, opponents only find out who cast it.
+		let source = match choices.face_down{
+			Some(_) => EventSource::Player(self.player(player_name)),
+			Option::None => EventSource::Card(player_name.to_string(), card_id),
+		};
+		Ok(vec![CurrentEvent::CardCasted(source)])
+	}
+
+	/// the player with priority passes it to the next player. Once everyone's passed in a row the
+	/// top of the stack resolves and the active player gets priority again. (If the stack's empty
+	/// nobody gets priority; the step is over.)
+	pub fn pass_priority(&mut self, player_name: &str) -> Result<Vec<CurrentEvent>, PriorityError>{
+		if self.priority.as_deref() != Some(player_name){
+			return Err(PriorityError::NotYourPriority);
+		}
+		if !self.pending_triggers.is_empty(){
+			return Err(PriorityError::TriggersWaiting);
+		}
+		self.passed_priority.push(player_name.to_string());
+		let in_game: Vec<String> = self.turn_order().into_iter()
+			.filter(|name| self.player_turn.players.iter().any(|player| player.name == *name && !player.has_left_game))
+			.collect();
+		if in_game.iter().all(|name| self.passed_priority.contains(name)){
+			self.passed_priority.clear();
+			if self.stack.is_empty(){
+				self.priority = Option::None;
+				return Ok(vec![]);
+			}
+			let events = self.resolve_top_of_stack();
+			let active_player = self.active_player().to_string();
+			self.give_priority(&active_player);
+			return Ok(events);
+		}
+		let position = in_game.iter().position(|name| name == player_name).unwrap_or(0);
+		let next = in_game[(position + 1) % in_game.len()].clone();
+		self.give_priority(&next);
+		Ok(vec![])
+	}
+
+	/// resolves whatever's on top of the stack. Permanent spells go onto the battlefield and
+	/// instants and sorceries go to the graveyard. A spell whose targets are all gone doesn't
+	/// resolve and just goes to the graveyard. (What spells and abilities actually do isn't a
+	/// thing yet.)
+	pub fn resolve_top_of_stack(&mut self) -> Vec<CurrentEvent>{
+		let spell = match self.stack.pop(){
+			Some(StackObject::Spell(spell)) => spell,
+			_ => return vec![],
+		};
+		let fizzled = !spell.targets.is_empty()
+			&& !spell.targets.iter().zip(&spell.restrictions).any(|(target, kinds)| self.target_is_legal(target, kinds));
+		let deck = match self.get_deck_mut(&spell.controller){
+			Some(deck) => deck,
+			Option::None => return vec![],
+		};
+		if let Some(kind) = spell.face_down{
+			return deck.put_onto_battlefield_face_down(spell.card_id, kind).into_iter().collect();
+		}
+		let card_types = &deck.cards[&spell.card_id].card.card_types;
+		let is_permanent = !card_types.contains(&CardType::Instant) && !card_types.contains(&CardType::Sorcery);
+		let to = if is_permanent && !fizzled { CardLocation::Battlefield } else { CardLocation::Graveyard };
+		deck.move_card_with_events(spell.card_id, CardLocation::Stack, to).unwrap_or_default()
+	}
+
+	/// whether something can be targeted by a target that has to be one of `kinds`: a player
+	/// still in the game, or a permanent, spell or card in the right zone of the right type.
+	pub fn target_is_legal(&self, target: &Target, kinds: &[TargetKind]) -> bool{
+		match target{
+			Target::Player(name) => kinds.contains(&TargetKind::Player)
+				&& self.player_turn.players.iter().any(|player| player.name == *name && !player.has_left_game),
+			Target::Card(player_name, card_id) => {
+				let Some(card) = self.get_deck(player_name).and_then(|deck| deck.get_card_immut(*card_id)) else {
+					return false;
+				};
+				let card_types = &card.card.card_types;
+				let location = card.visibility_behavior.current_location;
+				kinds.iter().any(|kind| match kind{
+					TargetKind::Permanent(card_type) if location == CardLocation::Battlefield =>
+						card_type.as_ref().is_none_or(|card_type| card_types.contains(card_type)),
+					TargetKind::Spell(card_type) if location == CardLocation::Stack =>
+						card_type.as_ref().is_none_or(|card_type| card_types.contains(card_type)),
+					TargetKind::Card(zone, card_type) if location == *zone =>
+						card_type.as_ref().is_none_or(|card_type| card_types.contains(card_type)),
+					_ => false,
+				})
 			},
-			turn_phase: TurnPhase::Untap
 		}
 	}
 }
@@ -163,8 +930,8 @@ mod tests {
 
 	#[test]
 	fn player_turn_change_turn_moves_to_next_player(){
-		let andrew = Player{name: "Andrew".to_string() };
-		let rory = Player{name: "Rory".to_string() };
+		let andrew = Player::new("Andrew");
+		let rory = Player::new("Rory");
 		let mut player_turn = PlayerTurn::new(vec![
 			andrew.clone()
 		,	rory.clone()
@@ -176,11 +943,152 @@ mod tests {
 		assert_eq!(player_turn.current_turn.name, andrew.name);
 	}
 
+	#[test]
+	fn games_can_be_replayed_from_their_seed_and_actions(){
+		let cards = vec![crate::deck::CardListItem("Forest".to_string(), 20), crate::deck::CardListItem("Opt".to_string(), 20)];
+		let outburst = crate::card_db::get_card_db().get_card("Hordeling Outburst").unwrap();
+		let goblin = crate::token::parse_token_creations(&outburst.description).remove(0).definition;
+		let mut state = GameState::with_seed(2024);
+		for action in [
+			Action::AddPlayer("Andrew".to_string()),
+			Action::AddPlayer("Rory".to_string()),
+			Action::AddDeck(Box::new(Deck::new(Player::new("Andrew"), &cards, &vec![]).unwrap())),
+			Action::RollForFirstTurn,
+			Action::ShuffleLibrary("Andrew".to_string()),
+			Action::CreateToken{ player_name: "Andrew".to_string(), definition: goblin },
+		] {
+			state = reducer(state, action);
+		}
+		let replayed = GameState::replay(state.rng.seed(), &state.action_log);
+		assert_eq!(replayed.player_turn.current_turn, state.player_turn.current_turn);
+		assert_eq!(
+			replayed.get_deck("Andrew").unwrap().get_library_card_names_in_order(),
+			state.get_deck("Andrew").unwrap().get_library_card_names_in_order()
+		);
+		// cards and tokens get the same ids, so logged actions still point at the right ones.
+		assert_eq!(replayed.get_deck("Andrew").unwrap().library, state.get_deck("Andrew").unwrap().library);
+		assert_eq!(replayed.get_deck("Andrew").unwrap().battlefield, state.get_deck("Andrew").unwrap().battlefield);
+		assert_eq!(replayed.action_log.len(), 6);
+	}
+
+	fn two_player_game(cards: &[crate::deck::CardListItem]) -> GameState{
+		let mut state = GameState::with_seed(1);
+		state.player_turn = PlayerTurn::new(vec![Player::new("Andrew"), Player::new("Rory")]);
+		state.add_deck(Deck::new(Player::new("Andrew"), &cards.to_vec(), &vec![]).unwrap());
+		state.add_deck(Deck::new(Player::new("Rory"), &cards.to_vec(), &vec![]).unwrap());
+		state
+	}
+
+	#[test]
+	fn drawing_from_an_empty_library_loses_when_state_based_actions_are_checked(){
+		let mut state = two_player_game(&[crate::deck::CardListItem("Forest".to_string(), 1)]);
+		assert!(matches!(state.draw_card("Andrew")[0], CurrentEvent::CardDrawn(_)));
+		assert!(state.draw_card("Andrew").is_empty());
+		assert!(!state.player_turn.players[0].has_left_game);
+
+		let events = state.check_state_based_actions();
+		let andrew = &state.player_turn.players[0];
+		assert!(andrew.has_left_game);
+		assert_eq!(events, vec![CurrentEvent::PlayerKilled(EventSource::Player(andrew.clone()))]);
+		assert!(!state.player_turn.players[1].has_left_game);
+		// they only lose once.
+		assert!(state.check_state_based_actions().is_empty());
+	}
+
+	#[test]
+	fn laboratory_maniac_wins_instead(){
+		let mut state = two_player_game(&[crate::deck::CardListItem("Laboratory Maniac".to_string(), 1)]);
+		let maniac = state.get_deck("Andrew").unwrap().library[0];
+		state.get_deck_mut("Andrew").unwrap()
+			.move_card(maniac, crate::card::CardLocation::Library, crate::card::CardLocation::Battlefield).unwrap();
+		let events = state.draw_card("Andrew");
+		assert_eq!(events, vec![CurrentEvent::PlayerWon(EventSource::Card("Andrew".to_string(), maniac))]);
+		assert!(!state.player_turn.players[0].drew_from_empty_library);
+		assert!(state.player_turn.players[1].has_left_game);
+		assert!(state.check_state_based_actions().is_empty());
+
+		let mut state = two_player_game(&[crate::deck::CardListItem("Jace, Wielder of Mysteries".to_string(), 1)]);
+		let jace = put(&mut state, "Rory", "Jace, Wielder of Mysteries", CardLocation::Battlefield);
+		let events = state.draw_card("Rory");
+		assert_eq!(events, vec![CurrentEvent::PlayerWon(EventSource::Card("Rory".to_string(), jace))]);
+	}
+
+	#[test]
+	fn london_mulligan_in_turn_order(){
+		let cards = vec![crate::deck::CardListItem("Forest".to_string(), 20), crate::deck::CardListItem("Opt".to_string(), 20)];
+		let mut state = GameState::with_seed(7);
+		state.player_turn = PlayerTurn::new(vec![Player::new("Andrew"), Player::new("Rory")]);
+		state.player_turn.change_turn();
+		state.add_deck(Deck::new(Player::new("Andrew"), &cards, &vec![]).unwrap());
+		state.add_deck(Deck::new(Player::new("Rory"), &cards, &vec![]).unwrap());
+		state.draw_opening_hands();
+		assert_eq!(state.get_deck("Andrew").unwrap().hand.len(), 7);
+		assert_eq!(state.next_mulligan_decision(), Some("Rory"));
+
+		assert_eq!(state.keep_hand("Andrew", &[]), Err(MulliganError::NotYourDecision));
+		state.mulligan("Rory").unwrap();
+		state.keep_hand("Andrew", &[]).unwrap();
+		assert!(!state.all_hands_kept());
+		assert_eq!(state.next_mulligan_decision(), Some("Rory"));
+		assert_eq!(state.keep_hand("Rory", &[]), Err(MulliganError::WrongNumberOfCards{expected: 1}));
+		let bottom = state.get_deck("Rory").unwrap().hand[0];
+		state.keep_hand("Rory", &[bottom]).unwrap();
+		assert_eq!(state.get_deck("Rory").unwrap().hand.len(), 6);
+		assert_eq!(state.get_deck("Rory").unwrap().library[0], bottom);
+		assert!(state.all_hands_kept());
+	}
+
+	#[test]
+	fn the_same_card_cant_go_on_the_bottom_twice(){
+		let cards = vec![crate::deck::CardListItem("Forest".to_string(), 20)];
+		let mut state = GameState::with_seed(7);
+		state.player_turn = PlayerTurn::new(vec![Player::new("Andrew")]);
+		state.add_deck(Deck::new(Player::new("Andrew"), &cards, &vec![]).unwrap());
+		state.draw_opening_hands();
+		state.mulligan("Andrew").unwrap();
+		state.mulligan("Andrew").unwrap();
+		let hand = state.get_deck("Andrew").unwrap().hand.clone();
+		assert_eq!(state.keep_hand("Andrew", &[hand[0], hand[0]]), Err(MulliganError::SameCardTwice));
+		assert_eq!(state.get_deck("Andrew").unwrap().hand.len(), 7);
+		state.keep_hand("Andrew", &[hand[0], hand[1]]).unwrap();
+		let deck = state.get_deck("Andrew").unwrap();
+		assert_eq!(deck.hand.len(), 5);
+		assert_eq!(deck.library.len(), 15);
+		assert!(deck.zones_are_consistent());
+	}
+
+	#[test]
+	fn the_first_commander_mulligan_is_free(){
+		let cards = vec![crate::deck::CardListItem("Plains".to_string(), 99)];
+		let mut state = GameState::with_seed(7);
+		state.rules = GameRules::commander();
+		state.player_turn = PlayerTurn::new(vec![Player::new("Blake")]);
+		state.add_deck(Deck::new(Player::new("Blake"), &cards, &vec![]).unwrap());
+		state.draw_opening_hands();
+		state.mulligan("Blake").unwrap();
+		assert_eq!(state.cards_to_bottom("Blake"), 0);
+		state.mulligan("Blake").unwrap();
+		assert_eq!(state.cards_to_bottom("Blake"), 1);
+	}
+
+	#[test]
+	fn twenty_one_damage_from_one_commander_kills_you(){
+		let mut player = Player::new("Andrew");
+		player.life = 40;
+		let (neyali, jor_kadeen) = (CardId::generate(), CardId::generate());
+		player.take_commander_damage(neyali, 15);
+		player.take_commander_damage(jor_kadeen, 15);
+		assert_eq!(player.life, 10);
+		assert!(!player.has_lost());
+		player.take_commander_damage(neyali, 6);
+		assert!(player.has_lost());
+	}
+
 	#[test]
 	fn player_turn_add_player_adds_player(){
-		let andrew = Player{name: "Andrew".to_string() };
-		let rory = Player{name: "Rory".to_string() };
-		let rosemary = Player{name: "Rosemary".to_string() };
+		let andrew = Player::new("Andrew");
+		let rory = Player::new("Rory");
+		let rosemary = Player::new("Rosemary");
 		let mut player_turn = PlayerTurn::new(vec![
 			andrew.clone()
 		,	rory.clone()
@@ -192,5 +1100,315 @@ mod tests {
 		player_turn.change_turn();
 		assert_eq!(player_turn.current_turn.name, rosemary.name);
 	}
-}
 
+	/// takes a card with this name out of a player's library and puts it somewhere.
+	fn put(state: &mut GameState, player_name: &str, card_name: &str, to: CardLocation) -> CardId{
+		let deck = state.get_deck_mut(player_name).unwrap();
+		let card_id = *deck.library.iter().find(|card_id| deck.cards[*card_id].name == card_name).unwrap();
+		deck.move_card(card_id, CardLocation::Library, to).unwrap();
+		card_id
+	}
+
+	fn spell_game() -> GameState{
+		use crate::deck::CardListItem;
+		let mut state = two_player_game(&[
+			CardListItem("Grizzly Bears".to_string(), 1),
+			CardListItem("Forest".to_string(), 2),
+			CardListItem("Mountain".to_string(), 2),
+			CardListItem("Lightning Bolt".to_string(), 1),
+			CardListItem("Abrade".to_string(), 1),
+			CardListItem("Murder".to_string(), 1),
+			CardListItem("Swamp".to_string(), 3),
+		]);
+		state.turn_phase = TurnPhase::PreCombatMainPhase;
+		state
+	}
+
+	#[test]
+	fn spells_resolve_once_everyone_passes_in_a_row(){
+		let mut state = spell_game();
+		let forests = vec![put(&mut state, "Andrew", "Forest", CardLocation::Battlefield), put(&mut state, "Andrew", "Forest", CardLocation::Battlefield)];
+		let bears = put(&mut state, "Andrew", "Grizzly Bears", CardLocation::Hand);
+		let choices = SpellChoices{ mana: forests.clone(), ..Default::default() };
+		assert_eq!(state.cast_spell("Andrew", bears, choices.clone()), Err(CastError::NoPriority));
+
+		state.give_priority("Andrew");
+		let events = state.cast_spell("Andrew", bears, choices).unwrap();
+		assert_eq!(events, vec![CurrentEvent::CardCasted(EventSource::Card("Andrew".to_string(), bears))]);
+		assert_eq!(state.stack.len(), 1);
+		assert_eq!(state.get_deck("Andrew").unwrap().stack, vec![bears]);
+		assert!(state.get_deck("Andrew").unwrap().cards[&forests[0]].battlefield_behavior.is_tapped());
+		assert_eq!(state.priority.as_deref(), Some("Andrew"));
+
+		assert_eq!(state.pass_priority("Rory"), Err(PriorityError::NotYourPriority));
+		assert!(state.pass_priority("Andrew").unwrap().is_empty());
+		assert_eq!(state.priority.as_deref(), Some("Rory"));
+		let events = state.pass_priority("Rory").unwrap();
+		assert_eq!(events, vec![CurrentEvent::CardPlacedOnBattlefield(EventSource::Card("Andrew".to_string(), bears))]);
+		assert!(state.stack.is_empty());
+		assert_eq!(state.get_deck("Andrew").unwrap().battlefield.len(), 3);
+		assert_eq!(state.priority.as_deref(), Some("Andrew"));
+
+		// with nothing on the stack, everyone passing ends the step.
+		state.pass_priority("Andrew").unwrap();
+		state.pass_priority("Rory").unwrap();
+		assert_eq!(state.priority, Option::None);
+	}
+
+	#[test]
+	fn sorcery_speed_spells_wait_for_an_empty_stack_in_your_main_phase(){
+		let mut state = spell_game();
+		let mountain = put(&mut state, "Rory", "Mountain", CardLocation::Battlefield);
+		let bolt = put(&mut state, "Rory", "Lightning Bolt", CardLocation::Hand);
+		let forests = vec![put(&mut state, "Andrew", "Forest", CardLocation::Battlefield), put(&mut state, "Andrew", "Forest", CardLocation::Battlefield)];
+		let bears = put(&mut state, "Andrew", "Grizzly Bears", CardLocation::Hand);
+		state.give_priority("Andrew");
+		state.pass_priority("Andrew").unwrap();
+
+		// Rory can bolt Andrew on Andrew's turn, since it's an instant.
+		let no_target = SpellChoices{ mana: vec![mountain], ..Default::default() };
+		assert_eq!(state.cast_spell("Rory", bolt, no_target), Err(CastError::WrongTargets));
+		let at_andrew = SpellChoices{ mana: vec![mountain], targets: vec![Target::Player("Andrew".to_string())], ..Default::default() };
+		state.cast_spell("Rory", bolt, at_andrew).unwrap();
+		state.pass_priority("Rory").unwrap();
+		// but Andrew can't cast a creature in response.
+		let choices = SpellChoices{ mana: forests, ..Default::default() };
+		assert_eq!(state.cast_spell("Andrew", bears, choices.clone()), Err(CastError::WrongTiming));
+		state.pass_priority("Andrew").unwrap();
+		assert_eq!(state.get_deck("Rory").unwrap().graveyard, vec![bolt]);
+		state.cast_spell("Andrew", bears, choices).unwrap();
+
+		state.turn_phase = TurnPhase::Upkeep;
+		let land = put(&mut state, "Andrew", "Mountain", CardLocation::Hand);
+		assert_eq!(state.cast_spell("Andrew", land, SpellChoices::default()), Err(CastError::NotASpell));
+	}
+
+	#[test]
+	fn modes_targets_and_mana_all_have_to_check_out(){
+		let mut state = spell_game();
+		let mountains = vec![put(&mut state, "Andrew", "Mountain", CardLocation::Battlefield), put(&mut state, "Andrew", "Mountain", CardLocation::Battlefield)];
+		let bears = put(&mut state, "Rory", "Grizzly Bears", CardLocation::Battlefield);
+		let abrade = put(&mut state, "Andrew", "Abrade", CardLocation::Hand);
+		state.give_priority("Andrew");
+		let target = vec![Target::Card("Rory".to_string(), bears)];
+		let both_modes = SpellChoices{ modes: vec![0, 1], targets: target.clone(), mana: mountains.clone(), ..Default::default() };
+		assert_eq!(state.cast_spell("Andrew", abrade, both_modes), Err(CastError::WrongModes));
+		let one_mountain = SpellChoices{ modes: vec![0], targets: target.clone(), mana: vec![mountains[0]], ..Default::default() };
+		assert_eq!(state.cast_spell("Andrew", abrade, one_mountain), Err(CastError::CantPay));
+		let same_mountain_twice = SpellChoices{ modes: vec![0], targets: target.clone(), mana: vec![mountains[0], mountains[0]], ..Default::default() };
+		assert_eq!(state.cast_spell("Andrew", abrade, same_mountain_twice), Err(CastError::CantPay));
+		let choices = SpellChoices{ modes: vec![0], targets: target, mana: mountains, ..Default::default() };
+		state.cast_spell("Andrew", abrade, choices).unwrap();
+		assert!(matches!(&state.stack[0], StackObject::Spell(spell) if spell.modes == vec![0]));
+	}
+
+	#[test]
+	fn targets_have_to_be_what_the_spell_says(){
+		let mut state = spell_game();
+		let swamps: Vec<CardId> = (0..3).map(|_| put(&mut state, "Andrew", "Swamp", CardLocation::Battlefield)).collect();
+		let mountain = put(&mut state, "Rory", "Mountain", CardLocation::Battlefield);
+		let bears = put(&mut state, "Rory", "Grizzly Bears", CardLocation::Battlefield);
+		let murder = put(&mut state, "Andrew", "Murder", CardLocation::Hand);
+		let bolt = put(&mut state, "Rory", "Lightning Bolt", CardLocation::Hand);
+		state.give_priority("Andrew");
+		let murder_at = |target: Target| SpellChoices{ targets: vec![target], mana: swamps.clone(), ..Default::default() };
+		assert_eq!(state.cast_spell("Andrew", murder, murder_at(Target::Player("Rory".to_string()))), Err(CastError::WrongTargets));
+		assert_eq!(state.cast_spell("Andrew", murder, murder_at(Target::Card("Rory".to_string(), mountain))), Err(CastError::WrongTargets));
+		state.cast_spell("Andrew", murder, murder_at(Target::Card("Rory".to_string(), bears))).unwrap();
+		state.pass_priority("Andrew").unwrap();
+
+		// bolt can hit a creature or a player, but not a land or a spell.
+		let bolt_at = |target: Target| SpellChoices{ targets: vec![target], mana: vec![mountain], ..Default::default() };
+		assert_eq!(state.cast_spell("Rory", bolt, bolt_at(Target::Card("Andrew".to_string(), murder))), Err(CastError::WrongTargets));
+		assert_eq!(state.cast_spell("Rory", bolt, bolt_at(Target::Card("Andrew".to_string(), swamps[0]))), Err(CastError::WrongTargets));
+		state.cast_spell("Rory", bolt, bolt_at(Target::Card("Rory".to_string(), bears))).unwrap();
+	}
+
+	#[test]
+	fn reanimation_targets_creature_cards_in_graveyards(){
+		use crate::deck::CardListItem;
+		let mut state = two_player_game(&[
+			CardListItem("Reanimate".to_string(), 1),
+			CardListItem("Swamp".to_string(), 1),
+			CardListItem("Grizzly Bears".to_string(), 2),
+			CardListItem("Murder".to_string(), 1),
+		]);
+		state.turn_phase = TurnPhase::PreCombatMainPhase;
+		let swamp = put(&mut state, "Andrew", "Swamp", CardLocation::Battlefield);
+		let reanimate = put(&mut state, "Andrew", "Reanimate", CardLocation::Hand);
+		let dead_bears = put(&mut state, "Rory", "Grizzly Bears", CardLocation::Graveyard);
+		let live_bears = put(&mut state, "Rory", "Grizzly Bears", CardLocation::Battlefield);
+		let dead_murder = put(&mut state, "Rory", "Murder", CardLocation::Graveyard);
+		state.give_priority("Andrew");
+		let reanimate_at = |card_id: CardId| SpellChoices{ targets: vec![Target::Card("Rory".to_string(), card_id)], mana: vec![swamp], ..Default::default() };
+		assert_eq!(state.cast_spell("Andrew", reanimate, reanimate_at(live_bears)), Err(CastError::WrongTargets));
+		assert_eq!(state.cast_spell("Andrew", reanimate, reanimate_at(dead_murder)), Err(CastError::WrongTargets));
+		state.cast_spell("Andrew", reanimate, reanimate_at(dead_bears)).unwrap();
+	}
+
+	#[test]
+	fn morphs_get_cast_face_down_for_three(){
+		use crate::deck::CardListItem;
+		use crate::face_down::FaceDownKind;
+		let mut state = two_player_game(&[
+			CardListItem("Hidden Dragonslayer".to_string(), 1),
+			CardListItem("Grizzly Bears".to_string(), 1),
+			CardListItem("Mountain".to_string(), 3),
+		]);
+		state.turn_phase = TurnPhase::PreCombatMainPhase;
+		let mountains: Vec<CardId> = (0..3).map(|_| put(&mut state, "Andrew", "Mountain", CardLocation::Battlefield)).collect();
+		let dragonslayer = put(&mut state, "Andrew", "Hidden Dragonslayer", CardLocation::Hand);
+		let bears = put(&mut state, "Andrew", "Grizzly Bears", CardLocation::Hand);
+		state.give_priority("Andrew");
+		let face_down = |kind| SpellChoices{ mana: mountains.clone(), face_down: Some(kind), ..Default::default() };
+		assert_eq!(state.cast_spell("Andrew", bears, face_down(FaceDownKind::Morph)), Err(CastError::CantCastFaceDown));
+		assert_eq!(state.cast_spell("Andrew", dragonslayer, face_down(FaceDownKind::Disguise)), Err(CastError::CantCastFaceDown));
+		// it's {1}{W} face up, but three mountains pay for it face down.
+		let events = state.cast_spell("Andrew", dragonslayer, face_down(FaceDownKind::Morph)).unwrap();
+		assert_eq!(events, vec![CurrentEvent::CardCasted(EventSource::Player(Player::new("Andrew")))]);
+		assert!(state.get_deck("Andrew").unwrap().cards[&dragonslayer].battlefield_behavior.is_face_down());
+
+		state.pass_priority("Andrew").unwrap();
+		let events = state.pass_priority("Rory").unwrap();
+		assert_eq!(events, vec![CurrentEvent::CardPlacedFaceDown(EventSource::Player(Player::new("Andrew")))]);
+		let card = &state.get_deck("Andrew").unwrap().cards[&dragonslayer];
+		assert_eq!(card.visibility_behavior.current_location, CardLocation::Battlefield);
+		assert!(card.battlefield_behavior.is_face_down());
+		assert_eq!((card.battlefield_behavior.power(), card.battlefield_behavior.toughness()), (2, 2));
+	}
+
+	#[test]
+	fn spells_with_no_legal_targets_left_dont_resolve(){
+		let mut state = spell_game();
+		let swamps: Vec<CardId> = (0..3).map(|_| put(&mut state, "Andrew", "Swamp", CardLocation::Battlefield)).collect();
+		let bears = put(&mut state, "Rory", "Grizzly Bears", CardLocation::Battlefield);
+		let murder = put(&mut state, "Andrew", "Murder", CardLocation::Hand);
+		state.give_priority("Andrew");
+		let choices = SpellChoices{ targets: vec![Target::Card("Rory".to_string(), bears)], mana: swamps, ..Default::default() };
+		state.cast_spell("Andrew", murder, choices).unwrap();
+		state.get_deck_mut("Rory").unwrap().move_card(bears, CardLocation::Battlefield, CardLocation::Hand).unwrap();
+		state.pass_priority("Andrew").unwrap();
+		let events = state.pass_priority("Rory").unwrap();
+		assert_eq!(events, vec![CurrentEvent::CardPlacedInGraveyard(EventSource::Card("Andrew".to_string(), murder))]);
+		assert_eq!(state.get_deck("Rory").unwrap().hand, vec![bears]);
+	}
+
+	#[test]
+	fn steps_only_end_once_everyone_passes_with_an_empty_stack(){
+		use crate::selectors::{SelectActivePlayer, SelectPriority, SelectWaitingOn};
+		let mut state = spell_game();
+		state.turn_phase = TurnPhase::Untap;
+		let bolt = put(&mut state, "Andrew", "Lightning Bolt", CardLocation::Hand);
+		let mountain = put(&mut state, "Andrew", "Mountain", CardLocation::Battlefield);
+		// nobody gets priority in the untap step.
+		assert_eq!(SelectPriority.select(&state), Option::None);
+		let mut state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.turn_phase, TurnPhase::Upkeep);
+		assert_eq!(SelectActivePlayer.select(&state), "Andrew");
+		assert_eq!(SelectWaitingOn.select(&state), vec!["Andrew".to_string()]);
+		let choices = SpellChoices{ targets: vec![Target::Player("Rory".to_string())], mana: vec![mountain], ..Default::default() };
+		state.cast_spell("Andrew", bolt, choices).unwrap();
+		state.pass_priority("Andrew").unwrap();
+		let state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.turn_phase, TurnPhase::Upkeep);
+		assert_eq!(SelectWaitingOn.select(&state), vec!["Rory".to_string()]);
+
+		// the bolt resolves and Andrew gets priority back, so the step still can't end.
+		let state = reducer(state, Action::PassPriority("Rory".to_string()));
+		assert!(state.stack.is_empty());
+		let state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.turn_phase, TurnPhase::Upkeep);
+		let state = reducer(state, Action::PassPriority("Andrew".to_string()));
+		let state = reducer(state, Action::PassPriority("Rory".to_string()));
+		assert!(SelectWaitingOn.select(&state).is_empty());
+		let state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.turn_phase, TurnPhase::Draw);
+		assert_eq!(SelectPriority.select(&state), Some("Andrew".to_string()));
+	}
+
+	#[test]
+	fn state_based_actions_from_getting_priority_happen_in_the_same_action(){
+		let mut state = spell_game();
+		state.turn_phase = TurnPhase::Cleanup;
+		state.player_turn.players[1].life = 0;
+		// something triggering in cleanup gives the active player priority, which checks
+		// state-based actions.
+		state.pending_triggers.push(PendingTrigger{
+			controller: "Andrew".to_string(),
+			source: CardId::generate(),
+			ability: TriggeredAbility{ event: EventKind::BeginningOfStep, conditions: vec![], effect: String::new() },
+			event: CurrentEvent::BeginningOfStep(TurnPhase::Cleanup),
+		});
+		let state = reducer(state, Action::ShuffleLibrary("Andrew".to_string()));
+		assert_eq!(state.priority.as_deref(), Some("Andrew"));
+		assert!(state.player_turn.players[1].has_left_game);
+		assert!(matches!(state.current_event(), CurrentEvent::PlayerKilled(_)));
+		assert!(state.events.pending().next().is_none());
+	}
+
+	#[test]
+	fn nobody_gets_priority_in_cleanup_unless_something_happens(){
+		let mut state = spell_game();
+		state.turn_phase = TurnPhase::End;
+		let state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.turn_phase, TurnPhase::Cleanup);
+		assert_eq!(state.priority, Option::None);
+		let state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.turn_phase, TurnPhase::PassTurn);
+
+		let mut state = spell_game();
+		state.turn_phase = TurnPhase::End;
+		state.player_turn.players[1].drew_from_empty_library = true;
+		let state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.turn_phase, TurnPhase::Cleanup);
+		assert_eq!(state.priority, Some("Andrew".to_string()));
+		assert!(state.player_turn.players[1].has_left_game);
+
+		// since players got priority, there's another cleanup step before the turn ends.
+		let state = reducer(state, Action::PassPriority("Andrew".to_string()));
+		let state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.turn_phase, TurnPhase::Cleanup);
+		assert_eq!(state.priority, Option::None);
+		let state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.turn_phase, TurnPhase::PassTurn);
+	}
+
+	#[test]
+	fn the_active_player_untaps_in_their_untap_step(){
+		let mut state = spell_game();
+		let forest = put(&mut state, "Andrew", "Forest", CardLocation::Battlefield);
+		let mountain = put(&mut state, "Rory", "Mountain", CardLocation::Battlefield);
+		state.get_deck_mut("Andrew").unwrap().get_card(forest).unwrap().battlefield_behavior.set_tapped(true);
+		state.get_deck_mut("Rory").unwrap().get_card(mountain).unwrap().battlefield_behavior.set_tapped(true);
+		state.turn_phase = TurnPhase::PassTurn;
+		state.begin_next_step();
+		assert_eq!(state.turn_phase, TurnPhase::Untap);
+		assert!(!state.get_deck("Andrew").unwrap().cards[&forest].battlefield_behavior.is_tapped());
+		assert!(state.get_deck("Rory").unwrap().cards[&mountain].battlefield_behavior.is_tapped());
+	}
+
+	#[test]
+	fn planeswalkers_get_one_loyalty_ability_every_turn(){
+		use crate::deck::CardListItem;
+		use crate::loyalty::LoyaltyError;
+		let mut state = two_player_game(&[CardListItem("Elspeth Tirel".to_string(), 1)]);
+		let elspeth = put(&mut state, "Andrew", "Elspeth Tirel", CardLocation::Battlefield);
+		let activate = |state: &mut GameState| state.get_deck_mut("Andrew").unwrap().get_card(elspeth).unwrap()
+			.activate_loyalty_ability(0, 0, true);
+		assert_eq!(activate(&mut state), Ok(()));
+		assert_eq!(activate(&mut state), Err(LoyaltyError::AlreadyActivatedThisTurn));
+
+		// Rory's turn doesn't count.
+		state.turn_phase = TurnPhase::PassTurn;
+		let mut state = reducer(state, Action::ProgressTurn);
+		state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.turn_phase, TurnPhase::Untap);
+		assert_eq!(activate(&mut state), Err(LoyaltyError::AlreadyActivatedThisTurn));
+
+		state.turn_phase = TurnPhase::PassTurn;
+		let mut state = reducer(state, Action::ProgressTurn);
+		state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.active_player(), "Andrew");
+		assert_eq!(activate(&mut state), Ok(()));
+	}
+}
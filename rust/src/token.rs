@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use regex::Regex;
+use crate::card::{Card, CardType};
+use crate::colors::Color;
+use crate::cost::parse_costs_better;
+
+/// Everything that makes a token the token it is. Tokens aren't in CardDB (mtgjson keeps them
+/// somewhere else entirely), so cards that make tokens describe them in their text and we build
+/// these from that.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct TokenDefinition {
+	/// the token's name. Unless the card says otherwise it's just the subtypes, like "Goblin".
+	pub name: String,
+	pub card_types: Vec<CardType>,
+	pub supertypes: Vec<String>,
+	pub subtypes: Vec<String>,
+	/// an empty vec means the token is colorless.
+	pub colors: Vec<Color>,
+	pub power: i32,
+	pub toughness: i32,
+	pub keywords: Vec<String>,
+}
+
+impl TokenDefinition {
+	/// turns the definition into a Card, so tokens can be used anywhere a RealCard can.
+	pub fn to_card(&self) -> Card {
+		let mut type_line: Vec<String> = self.supertypes.clone();
+		type_line.push("Token".to_string());
+		type_line.extend(self.card_types.iter().map(|card_type| format!("{:?}", card_type)));
+		let mut card_type = type_line.join(" ");
+		if !self.subtypes.is_empty() {
+			card_type = format!("{} — {}", card_type, self.subtypes.join(" "));
+		}
+
+		Card {
+			card_type,
+			card_types: self.card_types.clone(),
+			colors: self.colors.clone(),
+			color_identity: self.colors.clone(),
+			converted_mana_cost: 0,
+			description: self.keywords.join(", "),
+			keywords: self.keywords.clone(),
+			layout: "token".to_string(),
+			mana_cost: parse_costs_better(""),
+			mana_value: 0,
+			name: self.name.clone(),
+			loyalty: None,
+			power: self.power.to_string(),
+			subtypes: self.subtypes.clone(),
+			supertypes: self.supertypes.clone(),
+			toughness: self.toughness.to_string(),
+			legalities: HashMap::new(),
+		}
+	}
+}
+
+/// returns the Card for a token definition. Like CardDB this hands out &'static Cards; each
+/// different kind of token only ever gets made into a Card once, no matter how many goblins
+/// Siege-Gang Commander makes.
+pub fn get_token_card(definition: &TokenDefinition) -> &'static Card {
+	static TOKEN_DB: OnceLock<Mutex<HashMap<TokenDefinition, &'static Card>>> = OnceLock::new();
+	let mut token_db = TOKEN_DB.get_or_init(|| Mutex::new(HashMap::new())).lock().unwrap();
+	token_db
+		.entry(definition.clone())
+		.or_insert_with(|| Box::leak(Box::new(definition.to_card())))
+}
+
+/// How many tokens a card creates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenCount {
+	Fixed(u32),
+	/// "create X ..." or "create a ... for each ..."; has to be figured out when it resolves.
+	Variable,
+}
+
+/// One "create N X/Y color Subtype creature token(s) with ..." phrase from a card's text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenCreation {
+	pub count: TokenCount,
+	pub enters_tapped: bool,
+	pub definition: TokenDefinition,
+}
+
+fn parse_count(count: &str) -> TokenCount {
+	match count.to_lowercase().as_str() {
+		"a" | "an" | "one" => TokenCount::Fixed(1),
+		"two" => TokenCount::Fixed(2),
+		"three" => TokenCount::Fixed(3),
+		"four" => TokenCount::Fixed(4),
+		"five" => TokenCount::Fixed(5),
+		"six" => TokenCount::Fixed(6),
+		"seven" => TokenCount::Fixed(7),
+		"eight" => TokenCount::Fixed(8),
+		"nine" => TokenCount::Fixed(9),
+		"ten" => TokenCount::Fixed(10),
+		number => number.parse().map(TokenCount::Fixed).unwrap_or(TokenCount::Variable)
+	}
+}
+
+fn capitalize(word: &str) -> String {
+	let mut chars = word.chars();
+	match chars.next() {
+		Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+		None => String::new()
+	}
+}
+
+/// finds every creature token a card's text says it creates. Only handles the (very common)
+/// "create N X/Y color Subtype creature token(s) with keywords" wording; tokens like Treasures and
+/// Clues that aren't creatures aren't handled yet, and neither are X/X tokens whose size depends on
+/// the game (they're skipped rather than made as 0/0s).
+pub fn parse_token_creations(description: &str) -> Vec<TokenCreation> {
+	static CREATE: OnceLock<Regex> = OnceLock::new();
+	let create = CREATE.get_or_init(|| Regex::new(
+		r"(?i)create (a|an|one|two|three|four|five|six|seven|eight|nine|ten|x|\d+) (tapped )?(\d+|x)/(\d+|x) ([a-z ,'-]+?) creature tokens?( with [^.,]+?)?( named [^.,]+?)?( for each|\.|,| that|$)"
+	).unwrap());
+
+	create.captures_iter(description).filter_map(|captures| {
+		let mut count = parse_count(&captures[1]);
+		if &captures[8] == " for each" {
+			count = TokenCount::Variable;
+		}
+		let enters_tapped = captures.get(2).is_some();
+		let power = captures[3].parse().ok()?;
+		let toughness = captures[4].parse().ok()?;
+
+		let mut colors = vec![];
+		let mut card_types = vec![];
+		let mut supertypes = vec![];
+		let mut subtypes = vec![];
+		for word in captures[5].split([' ', ',']).filter(|word| !word.is_empty()) {
+			match word.to_lowercase().as_str() {
+				"and" | "colorless" => {},
+				"white" | "blue" | "black" | "red" | "green" => colors.push(Color::from_str(word).unwrap()),
+				"legendary" | "snow" => supertypes.push(capitalize(word)),
+				"artifact" | "enchantment" => card_types.push(CardType::from_str(&word.to_lowercase()).unwrap()),
+				_ => subtypes.push(word.to_string())
+			}
+		}
+		card_types.push(CardType::Creature);
+
+		let keywords = match captures.get(6) {
+			Some(keywords) => keywords.as_str()
+				.trim_start_matches(" with ")
+				.split(" and ")
+				.flat_map(|keyword| keyword.split(", "))
+				.map(|keyword| capitalize(keyword.trim()))
+				.filter(|keyword| !keyword.is_empty())
+				.collect(),
+			None => vec![]
+		};
+		let name = match captures.get(7) {
+			Some(name) => name.as_str().trim_start_matches(" named ").to_string(),
+			None => subtypes.join(" ")
+		};
+
+		Some(TokenCreation {
+			count,
+			enters_tapped,
+			definition: TokenDefinition {
+				name,
+				card_types,
+				supertypes,
+				subtypes,
+				colors,
+				power,
+				toughness,
+				keywords,
+			}
+		})
+	}).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_battle_screech_birds(){
+		let tokens = parse_token_creations("Create two 1/1 white Bird creature tokens with flying.\nFlashback—Tap three untapped white creatures you control.");
+		assert_eq!(tokens.len(), 1);
+		assert_eq!(tokens[0].count, TokenCount::Fixed(2));
+		assert_eq!(tokens[0].definition.name, "Bird");
+		assert_eq!(tokens[0].definition.colors, vec![Color::W]);
+		assert_eq!(tokens[0].definition.keywords, vec!["Flying".to_string()]);
+		assert_eq!((tokens[0].definition.power, tokens[0].definition.toughness), (1, 1));
+	}
+
+	#[test]
+	fn parses_siege_gang_goblins(){
+		let tokens = parse_token_creations("When Siege-Gang Commander enters the battlefield, create three 1/1 red Goblin creature tokens.");
+		assert_eq!(tokens[0].count, TokenCount::Fixed(3));
+		assert_eq!(tokens[0].definition.subtypes, vec!["Goblin".to_string()]);
+		assert_eq!(tokens[0].definition.colors, vec![Color::R]);
+		assert!(tokens[0].definition.keywords.is_empty());
+	}
+
+	#[test]
+	fn parses_multicolored_variable_tokens(){
+		let tokens = parse_token_creations("At the beginning of combat on your turn, put a muster counter on Assemble the Legion. Then create a 1/1 red and white Soldier creature token with haste for each muster counter on it.");
+		assert_eq!(tokens[0].count, TokenCount::Variable);
+		assert_eq!(tokens[0].definition.colors, vec![Color::R, Color::W]);
+		assert_eq!(tokens[0].definition.keywords, vec!["Haste".to_string()]);
+	}
+
+	#[test]
+	fn parses_colorless_artifact_creature_tokens(){
+		let tokens = parse_token_creations("When Myr Battlesphere enters the battlefield, create four 1/1 colorless Myr artifact creature tokens.");
+		assert_eq!(tokens[0].count, TokenCount::Fixed(4));
+		assert!(tokens[0].definition.colors.is_empty());
+		assert_eq!(tokens[0].definition.card_types, vec![CardType::Artifact, CardType::Creature]);
+		assert_eq!(tokens[0].definition.to_card().card_type, "Token Artifact Creature — Myr");
+	}
+
+	#[test]
+	fn parses_named_tokens(){
+		let tokens = parse_token_creations("{1}{R}, {T}: Create a 0/1 red Kobold creature token named Kobolds of Kher Keep.");
+		assert_eq!(tokens[0].definition.name, "Kobolds of Kher Keep");
+		assert_eq!(tokens[0].definition.subtypes, vec!["Kobold".to_string()]);
+	}
+
+	#[test]
+	fn skips_tokens_with_variable_power_and_toughness(){
+		let tokens = parse_token_creations("Create an X/X green Ooze creature token, where X is the number of creature cards in your graveyard. Then create a 1/1 green Saproling creature token.");
+		assert_eq!(tokens.len(), 1);
+		assert_eq!(tokens[0].definition.subtypes, vec!["Saproling".to_string()]);
+	}
+
+	#[test]
+	fn token_db_only_makes_each_token_once(){
+		let definition = parse_token_creations("Create a 1/1 white Spirit creature token with flying.")
+			.remove(0).definition;
+		let spirit1 = get_token_card(&definition);
+		let spirit2 = get_token_card(&definition);
+		assert!(std::ptr::eq(spirit1, spirit2));
+		assert_eq!(spirit1.power, "1");
+	}
+}
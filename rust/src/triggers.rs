@@ -0,0 +1,300 @@
+use std::sync::OnceLock;
+use regex::Regex;
+use crate::card::{Card, CardId, CardType};
+use crate::reducers::{CurrentEvent, EventKind, EventSource};
+use crate::state_manager::{GameState, TurnPhase};
+
+/// Something that has to be true about an event for a triggered ability to go off. "Whenever
+/// another creature you control enters" is AnotherCard + YouControl + HasType(Creature).
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerCondition {
+	/// the event is about the card with the ability, like "When ~ enters the battlefield".
+	ThisCard,
+	/// the event is about some other card.
+	AnotherCard,
+	/// the card the event is about is controlled (or was cast) by whoever controls the ability.
+	YouControl,
+	HasType(CardType),
+	/// "noncreature spell".
+	NotType(CardType),
+	/// "with power 2 or less".
+	PowerAtMost(i32),
+	/// the step that's beginning is this one.
+	Step(TurnPhase),
+	/// it's the turn of whoever controls the ability ("your upkeep").
+	YourTurn,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggeredAbility {
+	/// what kind of event it's waiting for.
+	pub event: EventKind,
+	/// all of these have to be true for it to trigger.
+	pub conditions: Vec<TriggerCondition>,
+	/// what the ability does, straight from the card text. (Effects aren't a thing yet.)
+	pub effect: String,
+}
+
+/// A triggered ability that's gone off and is waiting to be put on the stack.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingTrigger {
+	/// whoever controls the card with the ability.
+	pub controller: String,
+	pub source: CardId,
+	pub ability: TriggeredAbility,
+	/// the event that made it trigger.
+	pub event: CurrentEvent,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerError {
+	/// the order given isn't exactly the player's pending triggers, each one once.
+	WrongOrder,
+}
+
+/// finds the triggered abilities in a card's text. Handles "When ~ enters the battlefield",
+/// "Whenever another creature [with power N or less] [you control] enters [the battlefield]
+/// [under your control]", "Whenever you cast a [noncreature] spell" and "At the beginning of
+/// your/each upkeep" (and the other steps).
+pub fn parse_triggered_abilities(card: &Card) -> Vec<TriggeredAbility> {
+	static ENTERS_ITSELF: OnceLock<Regex> = OnceLock::new();
+	static ANOTHER_ENTERS: OnceLock<Regex> = OnceLock::new();
+	static CAST: OnceLock<Regex> = OnceLock::new();
+	static BEGINNING: OnceLock<Regex> = OnceLock::new();
+	let enters_itself = ENTERS_ITSELF.get_or_init(|| Regex::new(r"^When(?:ever)? (?:~|this \w+) enters(?: the battlefield)?, (.+)$").unwrap());
+	let another_enters = ANOTHER_ENTERS.get_or_init(|| Regex::new(
+		r"^Whenever another (\w+)(?: with power (\d+) or less)?( you control)? enters(?: the battlefield)?( under your control)?, (.+)$"
+	).unwrap());
+	let cast = CAST.get_or_init(|| Regex::new(r"^Whenever you cast an? (non(\w+) )?spell, (.+)$").unwrap());
+	let beginning = BEGINNING.get_or_init(|| Regex::new(r"^At the beginning of (your|each) (upkeep|draw step|precombat main phase|combat|end step), (.+)$").unwrap());
+
+	let mut abilities = vec![];
+	for line in card.description.lines() {
+		let line = line.replace(&card.name, "~");
+		if let Some(captures) = enters_itself.captures(&line) {
+			abilities.push(TriggeredAbility {
+				event: EventKind::CardPlacedOnBattlefield,
+				conditions: vec![TriggerCondition::ThisCard],
+				effect: captures[1].to_string(),
+			});
+		} else if let Some(captures) = another_enters.captures(&line) {
+			let mut conditions = vec![TriggerCondition::AnotherCard];
+			if let Ok(card_type) = captures[1].parse() {
+				conditions.push(TriggerCondition::HasType(card_type));
+			}
+			if let Some(power) = captures.get(2) {
+				conditions.push(TriggerCondition::PowerAtMost(power.as_str().parse().unwrap()));
+			}
+			if captures.get(3).is_some() || captures.get(4).is_some() {
+				conditions.push(TriggerCondition::YouControl);
+			}
+			abilities.push(TriggeredAbility {
+				event: EventKind::CardPlacedOnBattlefield,
+				conditions,
+				effect: captures[5].to_string(),
+			});
+		} else if let Some(captures) = cast.captures(&line) {
+			let mut conditions = vec![TriggerCondition::YouControl];
+			if let Some(Ok(card_type)) = captures.get(2).map(|card_type| card_type.as_str().parse()) {
+				conditions.push(TriggerCondition::NotType(card_type));
+			}
+			abilities.push(TriggeredAbility {
+				event: EventKind::CardCasted,
+				conditions,
+				effect: captures[3].to_string(),
+			});
+		} else if let Some(captures) = beginning.captures(&line) {
+			let step = match &captures[2] {
+				"upkeep" => TurnPhase::Upkeep,
+				"draw step" => TurnPhase::Draw,
+				"precombat main phase" => TurnPhase::PreCombatMainPhase,
+				"combat" => TurnPhase::CombatPhase,
+				_ => TurnPhase::End,
+			};
+			let mut conditions = vec![TriggerCondition::Step(step)];
+			if &captures[1] == "your" {
+				conditions.push(TriggerCondition::YourTurn);
+			}
+			abilities.push(TriggeredAbility {
+				event: EventKind::BeginningOfStep,
+				conditions,
+				effect: captures[3].to_string(),
+			});
+		}
+	}
+	abilities
+}
+
+impl TriggeredAbility {
+	/// whether an event makes this ability trigger. `controller` controls `source`, the card with
+	/// the ability.
+	pub fn triggers_on(&self, event: &CurrentEvent, state: &GameState, controller: &str, source: CardId) -> bool {
+		if EventKind::from(event) != self.event {
+			return false;
+		}
+		let event_card = match event {
+			CurrentEvent::BeginningOfStep(_) => None,
+			_ => match event_source(event) {
+				Some(EventSource::Card(player_name, card_id)) => Some((player_name.as_str(), *card_id)),
+				_ => None,
+			}
+		};
+		let real_card = event_card.and_then(|(player_name, card_id)| state.get_deck(player_name)?.get_card_immut(card_id));
+		self.conditions.iter().all(|condition| match condition {
+			TriggerCondition::ThisCard => event_card.map(|(_, card_id)| card_id) == Some(source),
+			TriggerCondition::AnotherCard => event_card.is_some_and(|(_, card_id)| card_id != source),
+			TriggerCondition::YouControl => event_card.is_some_and(|(player_name, _)| player_name == controller),
+			TriggerCondition::HasType(card_type) => real_card.is_some_and(|card| card.card.card_types.contains(card_type)),
+			TriggerCondition::NotType(card_type) => real_card.is_some_and(|card| !card.card.card_types.contains(card_type)),
+			TriggerCondition::PowerAtMost(power) =>
+				real_card.and_then(|card| card.card.power.parse::<i32>().ok()).is_some_and(|card_power| card_power <= *power),
+			TriggerCondition::Step(step) => matches!(event, CurrentEvent::BeginningOfStep(beginning) if beginning == step),
+			TriggerCondition::YourTurn => state.player_turn.current_turn.name == controller,
+		})
+	}
+}
+
+/// the EventSource of an event, for the ones that have one.
+fn event_source(event: &CurrentEvent) -> Option<&EventSource> {
+	match event {
+		CurrentEvent::CardPlacedOnBattlefield(source)
+		| CurrentEvent::CardRemovedFromBattlefield(source)
+		| CurrentEvent::CardPlacedInGraveyard(source)
+		| CurrentEvent::CardRemovedFromGraveyard(source)
+		| CurrentEvent::CardPlacedInExile(source)
+		| CurrentEvent::CardRemovedFromExile(source)
+		| CurrentEvent::CardCasted(source)
+		| CurrentEvent::CardDrawn(source)
+		| CurrentEvent::CardPutIntoHand(source) => Some(source),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::card::CardLocation;
+	use crate::card_db::get_card_db;
+	use crate::deck::{CardListItem, Deck};
+	use crate::reducers::{reducer, Action, ActionError};
+	use crate::stack::StackObject;
+	use crate::state_manager::{Player, PlayerTurn};
+
+	fn game(andrews_cards: &[(&str, u32)], rorys_cards: &[(&str, u32)]) -> GameState {
+		let list = |cards: &[(&str, u32)]| cards.iter().map(|(name, qty)| CardListItem(name.to_string(), *qty)).collect();
+		let mut state = GameState::with_seed(1);
+		state.player_turn = PlayerTurn::new(vec![Player::new("Andrew"), Player::new("Rory")]);
+		state.add_deck(Deck::new(Player::new("Andrew"), &list(andrews_cards), &vec![]).unwrap());
+		state.add_deck(Deck::new(Player::new("Rory"), &list(rorys_cards), &vec![]).unwrap());
+		state
+	}
+
+	fn put_onto_battlefield(state: GameState, player_name: &str, card_name: &str) -> (GameState, CardId) {
+		let card_id = state.get_deck(player_name).unwrap().library.iter()
+			.find(|card_id| state.get_deck(player_name).unwrap().cards[*card_id].name == card_name)
+			.cloned()
+			.unwrap();
+		let state = reducer(state, Action::MoveCard {
+			player_name: player_name.to_string(),
+			card_id,
+			from: CardLocation::Library,
+			to: CardLocation::Battlefield,
+		});
+		(state, card_id)
+	}
+
+	#[test]
+	fn parses_the_common_trigger_wordings(){
+		let db = get_card_db();
+		let siege_gang = parse_triggered_abilities(db.get_card("Siege-Gang Commander").unwrap());
+		assert_eq!(siege_gang[0].event, EventKind::CardPlacedOnBattlefield);
+		assert_eq!(siege_gang[0].conditions, vec![TriggerCondition::ThisCard]);
+		assert!(siege_gang[0].effect.starts_with("create three"));
+
+		let mentor = parse_triggered_abilities(db.get_card("Mentor of the Meek").unwrap());
+		assert_eq!(mentor[0].conditions, vec![
+			TriggerCondition::AnotherCard,
+			TriggerCondition::HasType(CardType::Creature),
+			TriggerCondition::PowerAtMost(2),
+			TriggerCondition::YouControl,
+		]);
+
+		let channeler = parse_triggered_abilities(db.get_card("Dragon's Rage Channeler").unwrap());
+		assert_eq!(channeler[0].event, EventKind::CardCasted);
+		assert_eq!(channeler[0].conditions, vec![TriggerCondition::YouControl, TriggerCondition::NotType(CardType::Creature)]);
+
+		let arena = parse_triggered_abilities(db.get_card("Phyrexian Arena").unwrap());
+		assert_eq!(arena[0].conditions, vec![TriggerCondition::Step(TurnPhase::Upkeep), TriggerCondition::YourTurn]);
+		assert!(parse_triggered_abilities(db.get_card("Grizzly Bears").unwrap()).is_empty());
+	}
+
+	#[test]
+	fn another_creature_you_control_entering_triggers_mentor(){
+		let state = game(&[("Mentor of the Meek", 1), ("Grizzly Bears", 1), ("Siege-Gang Commander", 1)], &[("Grizzly Bears", 1)]);
+		let (state, mentor) = put_onto_battlefield(state, "Andrew", "Mentor of the Meek");
+		assert!(state.pending_triggers.is_empty());
+		let (state, _) = put_onto_battlefield(state, "Rory", "Grizzly Bears");
+		assert!(state.pending_triggers.is_empty());
+		let (state, bears) = put_onto_battlefield(state, "Andrew", "Grizzly Bears");
+		assert_eq!(state.pending_triggers.len(), 1);
+		assert_eq!(state.pending_triggers[0].source, mentor);
+		assert_eq!(state.pending_triggers[0].event, CurrentEvent::CardPlacedOnBattlefield(EventSource::Card("Andrew".to_string(), bears)));
+
+		// siege-gang is a 2/2 too, so mentor triggers alongside its own enters trigger.
+		let (state, siege_gang) = put_onto_battlefield(state, "Andrew", "Siege-Gang Commander");
+		assert_eq!(state.pending_triggers.len(), 3);
+		assert!(state.pending_triggers.iter().any(|trigger| trigger.source == siege_gang));
+
+		// once mentor's gone it stops listening.
+		let state = reducer(state, Action::MoveCard {
+			player_name: "Andrew".to_string(),
+			card_id: mentor,
+			from: CardLocation::Battlefield,
+			to: CardLocation::Graveyard,
+		});
+		assert!(!state.triggered_abilities.contains_key(&mentor));
+	}
+
+	#[test]
+	fn upkeep_triggers_only_go_off_on_your_turn_and_you_pick_the_order(){
+		let state = game(&[("Phyrexian Arena", 2)], &[("Phyrexian Arena", 1)]);
+		let (state, first) = put_onto_battlefield(state, "Andrew", "Phyrexian Arena");
+		let (state, second) = put_onto_battlefield(state, "Andrew", "Phyrexian Arena");
+		let (state, _) = put_onto_battlefield(state, "Rory", "Phyrexian Arena");
+		let mut state = reducer(state, Action::ProgressTurnPhase);
+		assert_eq!(state.pending_triggers.len(), 2);
+		assert!(state.pending_triggers.iter().all(|trigger| trigger.controller == "Andrew"));
+
+		assert_eq!(state.order_triggers("Andrew", &[0, 0]), Err(TriggerError::WrongOrder));
+		assert_eq!(state.pending_triggers.len(), 2);
+		let state = reducer(state, Action::OrderTriggers { player_name: "Andrew".to_string(), order: vec![0, 0] });
+		assert_eq!(state.rejected_action, Some(ActionError::Trigger(TriggerError::WrongOrder)));
+		assert_eq!(state.pending_triggers.len(), 2);
+		// once they're in order they go on the stack, since Andrew has priority.
+		let state = reducer(state, Action::OrderTriggers { player_name: "Andrew".to_string(), order: vec![1, 0] });
+		assert_eq!(state.rejected_action, None);
+		assert!(state.pending_triggers.is_empty());
+		let sources: Vec<CardId> = state.stack.iter().map(|object| match object {
+			StackObject::Ability(trigger) => trigger.source,
+			StackObject::Spell(spell) => spell.card_id,
+		}).collect();
+		assert_eq!(sources, vec![second, first]);
+	}
+
+	#[test]
+	fn triggers_go_on_the_stack_in_apnap_order(){
+		let mut state = game(&[], &[]);
+		state.player_turn.change_turn();
+		let trigger = |controller: &str| PendingTrigger {
+			controller: controller.to_string(),
+			source: CardId::generate(),
+			ability: TriggeredAbility { event: EventKind::BeginningOfStep, conditions: vec![], effect: String::new() },
+			event: CurrentEvent::BeginningOfStep(TurnPhase::Upkeep),
+		};
+		state.pending_triggers = vec![trigger("Andrew"), trigger("Rory"), trigger("Andrew")];
+		state.put_triggers_on_stack();
+		let controllers: Vec<&str> = state.stack.iter().map(|object| object.controller()).collect();
+		// it's Rory's turn, so Rory's trigger goes on first and Andrew's resolve first.
+		assert_eq!(controllers, vec!["Rory", "Andrew", "Andrew"]);
+	}
+}